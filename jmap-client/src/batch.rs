@@ -0,0 +1,322 @@
+use crate::client::{method_error, JmapClient, EMAIL_BODY_PROPERTIES};
+use crate::error::JmapError;
+use crate::types::*;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::marker::PhantomData;
+
+/// Builds a single API request out of several method calls (RFC 8620 §3.3).
+/// Later calls can take their IDs from earlier results with
+/// [`Call::reference`], which the server resolves before running them.
+///
+/// ```ignore
+/// let mut batch = client.batch();
+/// let query = batch.email_query(&query)?;
+/// let get = batch.email_get(query.reference("/ids"), &["id", "subject"]);
+/// let response = batch.send().await?;
+/// let emails = response.get(&get)?.list;
+/// ```
+pub struct RequestBuilder<'a> {
+    client: &'a JmapClient,
+    calls: Vec<Invocation>,
+}
+
+/// A method call added to a [`RequestBuilder`]; `T` is its response type.
+#[derive(Debug)]
+pub struct Call<T> {
+    name: String,
+    call_id: String,
+    response: PhantomData<fn() -> T>,
+}
+
+impl<T> Call<T> {
+    /// Refer to part of this call's response by JSON Pointer, e.g. `/ids` or
+    /// `/list/*/emailIds`.
+    pub fn reference(&self, path: &str) -> ResultReference {
+        ResultReference {
+            result_of: self.call_id.clone(),
+            name: self.name.clone(),
+            path: path.to_string(),
+        }
+    }
+}
+
+/// IDs for a call: either given directly or taken from an earlier result.
+#[derive(Debug, Clone)]
+pub enum Ids {
+    List(Vec<String>),
+    Reference(ResultReference),
+}
+
+impl From<Vec<String>> for Ids {
+    fn from(ids: Vec<String>) -> Self {
+        Ids::List(ids)
+    }
+}
+
+impl From<&[String]> for Ids {
+    fn from(ids: &[String]) -> Self {
+        Ids::List(ids.to_vec())
+    }
+}
+
+impl From<ResultReference> for Ids {
+    fn from(reference: ResultReference) -> Self {
+        Ids::Reference(reference)
+    }
+}
+
+impl<'a> RequestBuilder<'a> {
+    pub(crate) fn new(client: &'a JmapClient) -> Self {
+        RequestBuilder {
+            client,
+            calls: vec![],
+        }
+    }
+
+    /// Add an arbitrary method call. `accountId` is filled in unless `args`
+    /// already sets it.
+    pub fn call<T>(&mut self, name: &str, mut args: Value) -> Call<T> {
+        if args.get("accountId").is_none() {
+            args["accountId"] = json!(self.client.account_id());
+        }
+        let call_id = format!("c{}", self.calls.len());
+        self.calls.push(Invocation {
+            name: name.to_string(),
+            args,
+            call_id: call_id.clone(),
+        });
+        Call {
+            name: name.to_string(),
+            call_id,
+            response: PhantomData,
+        }
+    }
+
+    /// Email/query. Fails rather than sending an unfiltered query if
+    /// `query` can't be serialized.
+    pub fn email_query(
+        &mut self,
+        query: &EmailQuery,
+    ) -> Result<Call<EmailQueryResponse>, JmapError> {
+        let args = serde_json::to_value(query)?;
+        Ok(self.call("Email/query", args))
+    }
+
+    /// Email/queryChanges for `query` (only its filter, sort and
+    /// collapseThreads matter). `up_to_id` is the last ID the client has
+    /// loaded; changes past it may be omitted.
+    pub fn email_query_changes(
+        &mut self,
+        query: &EmailQuery,
+        since_query_state: &str,
+        up_to_id: Option<&str>,
+    ) -> Call<EmailQueryChangesResponse> {
+        let mut args = json!({
+            "filter": query.filter,
+            "collapseThreads": query.collapse_threads,
+            "calculateTotal": query.calculate_total,
+            "sinceQueryState": since_query_state,
+            "upToId": up_to_id,
+        });
+        if !query.sort.is_empty() {
+            args["sort"] = json!(query.sort);
+        }
+        self.call("Email/queryChanges", args)
+    }
+
+    pub fn mailbox_changes(&mut self, since_state: &str) -> Call<ChangesResponse> {
+        self.call("Mailbox/changes", json!({ "sinceState": since_state }))
+    }
+
+    pub fn mailbox_get(&mut self, ids: impl Into<Ids>) -> Call<GetResponse<Mailbox>> {
+        let mut args = json!({});
+        set_ids(&mut args, "ids", ids.into());
+        self.call("Mailbox/get", args)
+    }
+
+    pub fn email_changes(&mut self, since_state: &str) -> Call<ChangesResponse> {
+        self.call("Email/changes", json!({ "sinceState": since_state }))
+    }
+
+    pub fn thread_changes(&mut self, since_state: &str) -> Call<ChangesResponse> {
+        self.call("Thread/changes", json!({ "sinceState": since_state }))
+    }
+
+    pub fn email_get(
+        &mut self,
+        ids: impl Into<Ids>,
+        properties: &[&str],
+    ) -> Call<GetResponse<Email>> {
+        let mut args = json!({ "properties": properties });
+        set_ids(&mut args, "ids", ids.into());
+        self.call("Email/get", args)
+    }
+
+    /// Email/get with everything the thread view needs, including the
+    /// decoded text and HTML body values.
+    pub fn email_get_bodies(&mut self, ids: impl Into<Ids>) -> Call<GetResponse<Email>> {
+        let mut args = json!({
+            "properties": EMAIL_BODY_PROPERTIES,
+            "fetchTextBodyValues": true,
+            "fetchHTMLBodyValues": true,
+        });
+        set_ids(&mut args, "ids", ids.into());
+        self.call("Email/get", args)
+    }
+
+    pub fn thread_get(&mut self, ids: impl Into<Ids>) -> Call<GetResponse<Thread>> {
+        let mut args = json!({});
+        set_ids(&mut args, "ids", ids.into());
+        self.call("Thread/get", args)
+    }
+
+    pub fn identity_get(&mut self, ids: impl Into<Ids>) -> Call<GetResponse<Identity>> {
+        let mut args = json!({});
+        set_ids(&mut args, "ids", ids.into());
+        self.call("Identity/get", args)
+    }
+
+    pub fn identity_changes(&mut self, since_state: &str) -> Call<ChangesResponse> {
+        self.call("Identity/changes", json!({ "sinceState": since_state }))
+    }
+
+    /// EmailSubmission/query, newest first.
+    pub fn email_submission_query(
+        &mut self,
+        filter: &EmailSubmissionFilter,
+        limit: Option<u64>,
+    ) -> Call<QueryResponse> {
+        self.call(
+            "EmailSubmission/query",
+            json!({
+                "filter": filter,
+                "sort": [{ "property": "sentAt", "isAscending": false }],
+                "limit": limit,
+            }),
+        )
+    }
+
+    pub fn email_submission_get(
+        &mut self,
+        ids: impl Into<Ids>,
+    ) -> Call<GetResponse<EmailSubmission>> {
+        let mut args = json!({});
+        set_ids(&mut args, "ids", ids.into());
+        self.call("EmailSubmission/get", args)
+    }
+
+    pub fn email_submission_changes(&mut self, since_state: &str) -> Call<ChangesResponse> {
+        self.call("EmailSubmission/changes", json!({ "sinceState": since_state }))
+    }
+
+    /// SearchSnippet/get for `email_ids`, which should come from an
+    /// Email/query run with the same filter.
+    pub fn search_snippet_get(
+        &mut self,
+        filter: Option<&EmailFilter>,
+        email_ids: impl Into<Ids>,
+    ) -> Call<GetResponse<SearchSnippet>> {
+        let mut args = json!({ "filter": filter });
+        set_ids(&mut args, "emailIds", email_ids.into());
+        self.call("SearchSnippet/get", args)
+    }
+
+    /// Send all calls in one API request. Method-level errors don't fail the
+    /// request; they are returned per call from [`BatchResponse::get`].
+    pub async fn send(self) -> Result<BatchResponse, JmapError> {
+        let response = self.client.send_request(self.calls).await?;
+        Ok(BatchResponse {
+            responses: response.method_responses,
+        })
+    }
+}
+
+/// Write `ids` to `args` as either `key` or a `#key` result reference.
+fn set_ids(args: &mut Value, key: &str, ids: Ids) {
+    match ids {
+        Ids::List(ids) => args[key] = json!(ids),
+        Ids::Reference(reference) => args[format!("#{key}")] = json!(reference),
+    }
+}
+
+/// The method responses from a sent [`RequestBuilder`].
+#[derive(Debug, Clone)]
+pub struct BatchResponse {
+    responses: Vec<Invocation>,
+}
+
+impl BatchResponse {
+    /// Parse the response to `call`, or return its method error.
+    pub fn get<T: DeserializeOwned>(&self, call: &Call<T>) -> Result<T, JmapError> {
+        let inv = self
+            .responses
+            .iter()
+            .find(|inv| inv.call_id == call.call_id)
+            .ok_or_else(|| JmapError::Api(format!("Missing response to {}", call.name)))?;
+
+        if inv.name == "error" {
+            return Err(method_error(inv));
+        }
+
+        Ok(serde_json::from_value(inv.args.clone())?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call<T>(name: &str, call_id: &str) -> Call<T> {
+        Call {
+            name: name.to_string(),
+            call_id: call_id.to_string(),
+            response: PhantomData,
+        }
+    }
+
+    #[test]
+    fn set_ids_writes_list_or_reference() {
+        let mut args = json!({});
+        set_ids(&mut args, "ids", vec!["e1".to_string()].into());
+        assert_eq!(args, json!({ "ids": ["e1"] }));
+
+        let query = call::<QueryResponse>("Email/query", "c0");
+        let mut args = json!({});
+        set_ids(&mut args, "emailIds", query.reference("/ids").into());
+        assert_eq!(
+            args,
+            json!({ "#emailIds": { "resultOf": "c0", "name": "Email/query", "path": "/ids" } })
+        );
+    }
+
+    #[test]
+    fn get_matches_responses_by_call_id() {
+        let response = BatchResponse {
+            responses: vec![
+                Invocation {
+                    name: "Thread/get".to_string(),
+                    args: json!({ "accountId": "a", "list": [], "notFound": ["t2"] }),
+                    call_id: "c1".to_string(),
+                },
+                Invocation {
+                    name: "error".to_string(),
+                    args: json!({ "type": "invalidResultReference" }),
+                    call_id: "c2".to_string(),
+                },
+            ],
+        };
+
+        let threads = response.get(&call::<GetResponse<Thread>>("Thread/get", "c1")).unwrap();
+        assert_eq!(threads.not_found, Some(vec!["t2".to_string()]));
+
+        let failed = response.get(&call::<GetResponse<Email>>("Email/get", "c2"));
+        assert!(matches!(
+            failed,
+            Err(JmapError::MethodError { type_, .. }) if type_ == "invalidResultReference"
+        ));
+
+        let missing = response.get(&call::<GetResponse<Email>>("Email/get", "c3"));
+        assert!(matches!(missing, Err(JmapError::Api(_))));
+    }
+}
@@ -1,8 +1,19 @@
+use crate::batch::{Call, RequestBuilder};
 use crate::error::JmapError;
 use crate::types::*;
 use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
 use serde_json::{json, Value};
 
+/// Email properties fetched for full display (thread view, replies).
+pub(crate) const EMAIL_BODY_PROPERTIES: &[&str] = &[
+    "id", "blobId", "threadId", "mailboxIds", "keywords",
+    "messageId", "inReplyTo", "references",
+    "from", "to", "cc", "bcc", "replyTo",
+    "subject", "sentAt", "receivedAt", "size",
+    "hasAttachment", "preview",
+    "textBody", "htmlBody", "attachments", "bodyValues",
+];
+
 const JMAP_CAPABILITIES: &[&str] = &[
     "urn:ietf:params:jmap:core",
     "urn:ietf:params:jmap:mail",
@@ -86,6 +97,26 @@ impl JmapClient {
     pub async fn api_request(
         &self,
         method_calls: Vec<Invocation>,
+    ) -> Result<JmapResponse, JmapError> {
+        let jmap_response = self.send_request(method_calls).await?;
+
+        // Check for error responses
+        if let Some(inv) = jmap_response.method_responses.iter().find(|inv| inv.name == "error") {
+            return Err(method_error(inv));
+        }
+
+        Ok(jmap_response)
+    }
+
+    /// Start a batch of method calls to send in one request.
+    pub fn batch(&self) -> RequestBuilder<'_> {
+        RequestBuilder::new(self)
+    }
+
+    /// Send method calls without checking for method-level errors.
+    pub(crate) async fn send_request(
+        &self,
+        method_calls: Vec<Invocation>,
     ) -> Result<JmapResponse, JmapError> {
         let request = JmapRequest {
             using: JMAP_CAPABILITIES.iter().map(|s| s.to_string()).collect(),
@@ -105,21 +136,7 @@ impl JmapClient {
             return Err(JmapError::Auth);
         }
 
-        let jmap_response: JmapResponse = response.error_for_status()?.json().await?;
-
-        // Check for error responses
-        for inv in &jmap_response.method_responses {
-            if inv.name == "error" {
-                let type_ = inv.args["type"]
-                    .as_str()
-                    .unwrap_or("unknown")
-                    .to_string();
-                let description = inv.args["description"].as_str().map(|s| s.to_string());
-                return Err(JmapError::MethodError { type_, description });
-            }
-        }
-
-        Ok(jmap_response)
+        Ok(response.error_for_status()?.json().await?)
     }
 
     /// Get all mailboxes for the account. Returns (mailboxes, state).
@@ -148,44 +165,159 @@ impl JmapClient {
         Ok((mailboxes, state))
     }
 
-    /// Query emails in a mailbox, sorted by receivedAt descending.
-    /// Returns (email_ids, total_count).
-    pub async fn query_emails(
+    /// Create a mailbox, optionally as a child of `parent_id`. Returns the new ID.
+    pub async fn create_mailbox(
         &self,
-        mailbox_id: &str,
-        position: u64,
-        limit: u64,
-    ) -> Result<(Vec<String>, u64), JmapError> {
+        name: &str,
+        parent_id: Option<&str>,
+    ) -> Result<String, JmapError> {
         let response = self
             .api_request(vec![Invocation {
-                name: "Email/query".to_string(),
+                name: "Mailbox/set".to_string(),
                 args: json!({
                     "accountId": self.account_id,
-                    "filter": {
-                        "inMailbox": mailbox_id,
+                    "create": {
+                        "mb0": {
+                            "name": name,
+                            "parentId": parent_id,
+                        },
                     },
-                    "sort": [{
-                        "property": "receivedAt",
-                        "isAscending": false,
-                    }],
-                    "collapseThreads": true,
-                    "position": position,
-                    "limit": limit,
                 }),
-                call_id: "q0".to_string(),
+                call_id: "ms0".to_string(),
             }])
             .await?;
 
         let args = &response.method_responses[0].args;
-        let ids: Vec<String> = args["ids"]
-            .as_array()
-            .unwrap_or(&vec![])
-            .iter()
-            .filter_map(|v| v.as_str().map(|s| s.to_string()))
-            .collect();
-        let total = args["total"].as_u64().unwrap_or(0);
+        check_set_errors(args, "notCreated")?;
+        args["created"]["mb0"]["id"]
+            .as_str()
+            .map(|s| s.to_string())
+            .ok_or_else(|| JmapError::Api("Missing id in Mailbox/set response".to_string()))
+    }
+
+    /// Rename a mailbox.
+    pub async fn rename_mailbox(&self, mailbox_id: &str, name: &str) -> Result<(), JmapError> {
+        self.update_mailbox(mailbox_id, json!({ "name": name })).await
+    }
 
-        Ok((ids, total))
+    /// Move a mailbox under a new parent, or to the top level if `None`.
+    pub async fn move_mailbox(
+        &self,
+        mailbox_id: &str,
+        parent_id: Option<&str>,
+    ) -> Result<(), JmapError> {
+        self.update_mailbox(mailbox_id, json!({ "parentId": parent_id }))
+            .await
+    }
+
+    async fn update_mailbox(&self, mailbox_id: &str, patch: Value) -> Result<(), JmapError> {
+        let response = self
+            .api_request(vec![Invocation {
+                name: "Mailbox/set".to_string(),
+                args: json!({
+                    "accountId": self.account_id,
+                    "update": { mailbox_id: patch },
+                }),
+                call_id: "ms0".to_string(),
+            }])
+            .await?;
+
+        check_set_errors(&response.method_responses[0].args, "notUpdated")
+    }
+
+    /// Destroy a mailbox. With `remove_emails`, emails only in this mailbox
+    /// are destroyed too; otherwise a non-empty mailbox fails with
+    /// `mailboxHasEmail`. A mailbox with children fails with `mailboxHasChild`.
+    pub async fn destroy_mailbox(
+        &self,
+        mailbox_id: &str,
+        remove_emails: bool,
+    ) -> Result<(), JmapError> {
+        let response = self
+            .api_request(vec![Invocation {
+                name: "Mailbox/set".to_string(),
+                args: json!({
+                    "accountId": self.account_id,
+                    "destroy": [mailbox_id],
+                    "onDestroyRemoveEmails": remove_emails,
+                }),
+                call_id: "ms0".to_string(),
+            }])
+            .await?;
+
+        check_set_errors(&response.method_responses[0].args, "notDestroyed")
+    }
+
+    /// Run an Email/query.
+    pub async fn query_emails(&self, query: &EmailQuery) -> Result<EmailQueryResponse, JmapError> {
+        let response = self
+            .api_request(vec![Invocation {
+                name: "Email/query".to_string(),
+                args: self.email_query_args(query)?,
+                call_id: "q0".to_string(),
+            }])
+            .await?;
+
+        Ok(serde_json::from_value(response.method_responses[0].args.clone())?)
+    }
+
+    /// Get changes to the results of `query` since `since_query_state`.
+    /// Fails with `cannotCalculateChanges` if the server can't, in which
+    /// case the query must be rerun.
+    pub async fn query_email_changes(
+        &self,
+        query: &EmailQuery,
+        since_query_state: &str,
+        up_to_id: Option<&str>,
+    ) -> Result<EmailQueryChangesResponse, JmapError> {
+        let mut batch = self.batch();
+        let call = batch.email_query_changes(query, since_query_state, up_to_id);
+        batch.send().await?.get(&call)
+    }
+
+    /// Run an Email/query and fetch search snippets for the returned page in
+    /// the same request, passing the IDs via a result reference.
+    pub async fn query_emails_with_snippets(
+        &self,
+        query: &EmailQuery,
+    ) -> Result<(EmailQueryResponse, Vec<SearchSnippet>), JmapError> {
+        let mut batch = self.batch();
+        let query_call = batch.email_query(query)?;
+        let ids = query_call.reference("/ids");
+        let snippet_call = batch.search_snippet_get(query.filter.as_ref(), ids);
+        let response = batch.send().await?;
+
+        Ok((response.get(&query_call)?, response.get(&snippet_call)?.list))
+    }
+
+    fn email_query_args(&self, query: &EmailQuery) -> Result<Value, JmapError> {
+        let mut args = serde_json::to_value(query)?;
+        args["accountId"] = json!(self.account_id);
+        Ok(args)
+    }
+
+    /// Find an email by its Message-ID header (without angle brackets).
+    /// Only the `id` and `keywords` properties are fetched.
+    pub async fn find_email_by_message_id(
+        &self,
+        message_id: &str,
+    ) -> Result<Option<Email>, JmapError> {
+        let query = EmailQuery {
+            filter: Some(
+                EmailFilterCondition {
+                    header: Some(vec!["Message-ID".to_string(), message_id.to_string()]),
+                    ..Default::default()
+                }
+                .into(),
+            ),
+            limit: Some(1),
+            ..Default::default()
+        };
+        let mut batch = self.batch();
+        let query_call = batch.email_query(&query)?;
+        let get_call = batch.email_get(query_call.reference("/ids"), &["id", "keywords"]);
+        let response = batch.send().await?;
+        Ok(response.get(&get_call)?.list.into_iter().next())
     }
 
     /// Get emails by IDs with specified properties. Returns (emails, state).
@@ -260,14 +392,9 @@ impl JmapClient {
                 args: json!({
                     "accountId": self.account_id,
                     "ids": ids,
-                    "properties": [
-                        "id", "blobId", "threadId", "mailboxIds", "keywords",
-                        "from", "to", "cc", "bcc", "replyTo",
-                        "subject", "sentAt", "receivedAt",
-                        "hasAttachment", "preview",
-                        "textBody", "htmlBody", "bodyValues"
-                    ],
+                    "properties": EMAIL_BODY_PROPERTIES,
                     "fetchTextBodyValues": true,
+                    "fetchHTMLBodyValues": true,
                 }),
                 call_id: "eb0".to_string(),
             }])
@@ -281,8 +408,8 @@ impl JmapClient {
         Ok(emails)
     }
 
-    /// Get all identities for the account.
-    pub async fn get_identities(&self) -> Result<Vec<Identity>, JmapError> {
+    /// Get all identities for the account. Returns (identities, state).
+    pub async fn get_identities(&self) -> Result<(Vec<Identity>, String), JmapError> {
         let response = self
             .api_request(vec![Invocation {
                 name: "Identity/get".to_string(),
@@ -294,55 +421,142 @@ impl JmapClient {
             }])
             .await?;
 
-        let list = response.method_responses[0].args["list"]
+        let args = &response.method_responses[0].args;
+        let list = args["list"]
             .as_array()
             .ok_or_else(|| JmapError::Api("Missing list in Identity/get response".to_string()))?;
+        let state = args["state"]
+            .as_str()
+            .unwrap_or("")
+            .to_string();
 
         let identities: Vec<Identity> = serde_json::from_value(Value::Array(list.clone()))?;
-        Ok(identities)
+        Ok((identities, state))
+    }
+
+    /// Create an identity for `email`. The server may refuse addresses the
+    /// user isn't allowed to send from (`forbiddenFrom`). Returns the new ID.
+    pub async fn create_identity(
+        &self,
+        email: &str,
+        properties: &IdentityProperties,
+    ) -> Result<String, JmapError> {
+        let mut identity = serde_json::to_value(properties)?;
+        identity["email"] = json!(email);
+        let response = self
+            .api_request(vec![Invocation {
+                name: "Identity/set".to_string(),
+                args: json!({
+                    "accountId": self.account_id,
+                    "create": {
+                        "id0": identity,
+                    },
+                }),
+                call_id: "is0".to_string(),
+            }])
+            .await?;
+
+        let args = &response.method_responses[0].args;
+        check_set_errors(args, "notCreated")?;
+        args["created"]["id0"]["id"]
+            .as_str()
+            .map(|s| s.to_string())
+            .ok_or_else(|| JmapError::Api("Missing id in Identity/set response".to_string()))
+    }
+
+    /// Update an identity's name, Reply-To, Bcc and signatures.
+    pub async fn update_identity(
+        &self,
+        identity_id: &str,
+        properties: &IdentityProperties,
+    ) -> Result<(), JmapError> {
+        let response = self
+            .api_request(vec![Invocation {
+                name: "Identity/set".to_string(),
+                args: json!({
+                    "accountId": self.account_id,
+                    "update": {
+                        identity_id: properties,
+                    },
+                }),
+                call_id: "is0".to_string(),
+            }])
+            .await?;
+
+        check_set_errors(&response.method_responses[0].args, "notUpdated")
+    }
+
+    /// Destroy an identity. Only possible when its `mayDelete` is true.
+    pub async fn destroy_identity(&self, identity_id: &str) -> Result<(), JmapError> {
+        let response = self
+            .api_request(vec![Invocation {
+                name: "Identity/set".to_string(),
+                args: json!({
+                    "accountId": self.account_id,
+                    "destroy": [identity_id],
+                }),
+                call_id: "is0".to_string(),
+            }])
+            .await?;
+
+        check_set_errors(&response.method_responses[0].args, "notDestroyed")
+    }
+
+    /// Apply Identity/changes since `since_state` to `identities`, fetching
+    /// created and updated identities in the same request. Returns the new
+    /// state. Fails with `cannotCalculateChanges` if the server can't, in
+    /// which case the identities must be refetched.
+    pub async fn sync_identities(
+        &self,
+        identities: &mut Vec<Identity>,
+        since_state: &str,
+    ) -> Result<String, JmapError> {
+        let mut since = since_state.to_string();
+        loop {
+            let mut batch = self.batch();
+            let changes_call = batch.identity_changes(&since);
+            let created_call = batch.identity_get(changes_call.reference("/created"));
+            let updated_call = batch.identity_get(changes_call.reference("/updated"));
+            let response = batch.send().await?;
+            let changes = response.get(&changes_call)?;
+            let created = response.get(&created_call)?;
+            let updated = response.get(&updated_call)?;
+
+            identities.retain(|i| !changes.destroyed.contains(&i.id));
+            for identity in created.list.into_iter().chain(updated.list) {
+                match identities.iter_mut().find(|i| i.id == identity.id) {
+                    Some(existing) => *existing = identity,
+                    None => identities.push(identity),
+                }
+            }
+
+            since = changes.new_state;
+            if !changes.has_more_changes {
+                return Ok(since);
+            }
+        }
     }
 
     /// Create a draft email and submit it in a single API request.
-    #[allow(clippy::too_many_arguments)]
+    /// Fails without contacting the server if the attachments exceed
+    /// `maxSizeAttachmentsPerEmail`.
     pub async fn send_email(
         &self,
         identity_id: &str,
-        from: &[EmailAddress],
-        to: &[EmailAddress],
-        cc: &[EmailAddress],
-        bcc: &[EmailAddress],
-        subject: &str,
-        body: &str,
+        email: &OutgoingEmail,
         drafts_mailbox_id: &str,
         sent_mailbox_id: &str,
-    ) -> Result<(), JmapError> {
-        let mut mailbox_ids = serde_json::Map::new();
-        mailbox_ids.insert(drafts_mailbox_id.to_string(), json!(true));
-
-        let mut email_create = json!({
-            "mailboxIds": mailbox_ids,
-            "from": from,
-            "to": to,
-            "subject": subject,
-            "keywords": { "$seen": true, "$draft": true },
-            "textBody": [{
-                "partId": "body",
-                "type": "text/plain",
-            }],
-            "bodyValues": {
-                "body": {
-                    "value": body,
-                },
-            },
-        });
-
-        if !cc.is_empty() {
-            email_create["cc"] = json!(cc);
-        }
-        if !bcc.is_empty() {
-            email_create["bcc"] = json!(bcc);
+    ) -> Result<SentEmail, JmapError> {
+        if let Some(mail) = self.mail_capabilities() {
+            let size = email.attachments_size();
+            let max = mail.max_size_attachments_per_email;
+            if max > 0 && size > max {
+                return Err(JmapError::AttachmentsTooLarge { size, max });
+            }
         }
 
+        let email_create = email_create_args(email, drafts_mailbox_id);
+
         // Move from drafts to sent on successful submission
         let mut update_on_success = serde_json::Map::new();
         let mut mailbox_update = serde_json::Map::new();
@@ -351,6 +565,9 @@ impl JmapClient {
         mailbox_update.insert("keywords/$draft".to_string(), json!(null));
         update_on_success.insert("#emailToSend".to_string(), json!(mailbox_update));
 
+        // A draft that no longer exists is ignored; it may have been
+        // destroyed by an earlier attempt at sending this message.
+        let destroy: Vec<&String> = email.replaces_draft.iter().collect();
         let method_calls = vec![
             Invocation {
                 name: "Email/set".to_string(),
@@ -359,6 +576,7 @@ impl JmapClient {
                     "create": {
                         "emailToSend": email_create,
                     },
+                    "destroy": destroy,
                 }),
                 call_id: "s0".to_string(),
             },
@@ -370,6 +588,7 @@ impl JmapClient {
                         "sub0": {
                             "identityId": identity_id,
                             "emailId": "#emailToSend",
+                            "envelope": held_envelope(email),
                         },
                     },
                     "onSuccessUpdateEmail": update_on_success,
@@ -382,29 +601,296 @@ impl JmapClient {
 
         // Check Email/set for errors
         for inv in &response.method_responses {
-            if inv.name == "Email/set" {
-                if let Some(not_created) = inv.args["notCreated"].as_object() {
-                    if let Some(err) = not_created.get("emailToSend") {
-                        let type_ = err["type"].as_str().unwrap_or("unknown").to_string();
-                        let description = err["description"].as_str().map(|s| s.to_string());
-                        return Err(JmapError::MethodError { type_, description });
-                    }
-                }
+            if inv.name == "Email/set"
+                && let Some(not_created) = inv.args["notCreated"].as_object()
+                && let Some(err) = not_created.get("emailToSend")
+            {
+                let type_ = err["type"].as_str().unwrap_or("unknown").to_string();
+                let description = err["description"].as_str().map(|s| s.to_string());
+                return Err(JmapError::MethodError { type_, description });
             }
-            if inv.name == "EmailSubmission/set" {
-                if let Some(not_created) = inv.args["notCreated"].as_object() {
-                    if let Some(err) = not_created.get("sub0") {
-                        let type_ = err["type"].as_str().unwrap_or("unknown").to_string();
-                        let description = err["description"].as_str().map(|s| s.to_string());
-                        return Err(JmapError::MethodError { type_, description });
-                    }
-                }
+            if inv.name == "EmailSubmission/set"
+                && let Some(not_created) = inv.args["notCreated"].as_object()
+                && let Some(err) = not_created.get("sub0")
+            {
+                let type_ = err["type"].as_str().unwrap_or("unknown").to_string();
+                let description = err["description"].as_str().map(|s| s.to_string());
+                return Err(JmapError::MethodError { type_, description });
             }
         }
 
+        let created_id = |method: &str, key: &str| {
+            response
+                .method_responses
+                .iter()
+                .find(|inv| inv.name == method)
+                .and_then(|inv| inv.args["created"][key]["id"].as_str())
+                .map(|id| id.to_string())
+                .ok_or_else(|| JmapError::Api(format!("Missing id in {method} response")))
+        };
+        let sent = SentEmail {
+            email_id: created_id("Email/set", "emailToSend")?,
+            submission_id: created_id("EmailSubmission/set", "sub0")?,
+        };
+
+        // The message is already on its way, so failing to mark the
+        // originals isn't reported as a send failure. A held message can
+        // still be canceled, so its originals are left to the caller.
+        if email.release.is_none() && !email.forwarded.is_empty() {
+            let _ = self.set_keyword(&email.forwarded, "$forwarded", true).await;
+        }
+
+        Ok(sent)
+    }
+
+    /// Cancel a held submission before it's released and put its email
+    /// back in Drafts. Fails with `cannotUnsend` once it has gone out.
+    pub async fn cancel_submission(
+        &self,
+        submission_id: &str,
+        drafts_mailbox_id: &str,
+        sent_mailbox_id: &str,
+    ) -> Result<(), JmapError> {
+        let mut update_on_success = serde_json::Map::new();
+        let mut mailbox_update = serde_json::Map::new();
+        mailbox_update.insert(format!("mailboxIds/{sent_mailbox_id}"), json!(null));
+        mailbox_update.insert(format!("mailboxIds/{drafts_mailbox_id}"), json!(true));
+        mailbox_update.insert("keywords/$draft".to_string(), json!(true));
+        update_on_success.insert(submission_id.to_string(), json!(mailbox_update));
+
+        let response = self
+            .api_request(vec![Invocation {
+                name: "EmailSubmission/set".to_string(),
+                args: json!({
+                    "accountId": self.account_id,
+                    "update": {
+                        submission_id: { "undoStatus": "canceled" },
+                    },
+                    "onSuccessUpdateEmail": update_on_success,
+                }),
+                call_id: "es0".to_string(),
+            }])
+            .await?;
+
+        let args = &response.method_responses[0].args;
+        check_set_errors(args, "notUpdated")?;
+        // The email update runs as an implicit Email/set after the cancel
+        if let Some(inv) = response.method_responses.iter().find(|inv| inv.name == "Email/set") {
+            check_set_errors(&inv.args, "notUpdated")?;
+        }
         Ok(())
     }
 
+    /// Submit an existing email for delivery without creating a new one.
+    /// With an explicit `envelope` the message goes to its `rcptTo`
+    /// addresses rather than those in its headers, which is how a message
+    /// is redirected unchanged. Returns the EmailSubmission ID.
+    pub async fn submit_email(
+        &self,
+        identity_id: &str,
+        email_id: &str,
+        envelope: Option<&Envelope>,
+    ) -> Result<String, JmapError> {
+        let response = self
+            .api_request(vec![Invocation {
+                name: "EmailSubmission/set".to_string(),
+                args: json!({
+                    "accountId": self.account_id,
+                    "create": {
+                        "sub0": {
+                            "identityId": identity_id,
+                            "emailId": email_id,
+                            "envelope": envelope,
+                        },
+                    },
+                }),
+                call_id: "es0".to_string(),
+            }])
+            .await?;
+
+        let args = &response.method_responses[0].args;
+        check_set_errors(args, "notCreated")?;
+        args["created"]["sub0"]["id"]
+            .as_str()
+            .map(|s| s.to_string())
+            .ok_or_else(|| JmapError::Api("Missing id in EmailSubmission/set response".to_string()))
+    }
+
+    /// Save `email` as a draft in the Drafts mailbox, destroying the
+    /// previous version `replaces` in the same call since emails are
+    /// immutable. Returns the new draft's ID.
+    pub async fn save_draft(
+        &self,
+        email: &OutgoingEmail,
+        drafts_mailbox_id: &str,
+        replaces: Option<&str>,
+    ) -> Result<String, JmapError> {
+        let destroy: Vec<&str> = replaces.into_iter().collect();
+        let method_calls = vec![Invocation {
+            name: "Email/set".to_string(),
+            args: json!({
+                "accountId": self.account_id,
+                "create": {
+                    "draft": email_create_args(email, drafts_mailbox_id),
+                },
+                "destroy": destroy,
+            }),
+            call_id: "d0".to_string(),
+        }];
+
+        let response = self.api_request(method_calls).await?;
+        let inv = response
+            .method_responses
+            .first()
+            .ok_or_else(|| JmapError::Api("Empty response".to_string()))?;
+        check_set_errors(&inv.args, "notCreated")?;
+        let id = inv.args["created"]["draft"]["id"]
+            .as_str()
+            .map(|id| id.to_string())
+            .ok_or_else(|| JmapError::Api("Draft was not created".to_string()))?;
+
+        // Never leave two copies: if the old version couldn't be destroyed
+        // (other than being gone already), drop the new one and fail
+        let old_gone =
+            replaces.is_some_and(|old| inv.args["notDestroyed"][old]["type"] == "notFound");
+        if !old_gone && let Err(e) = check_set_errors(&inv.args, "notDestroyed") {
+            let _ = self.destroy_emails(std::slice::from_ref(&id)).await;
+            return Err(e);
+        }
+        Ok(id)
+    }
+
+    /// Set or clear a keyword (e.g. "$seen", "$flagged") on one or more emails.
+    pub async fn set_keyword(
+        &self,
+        email_ids: &[String],
+        keyword: &str,
+        enabled: bool,
+    ) -> Result<(), JmapError> {
+        let path = format!("keywords/{}", escape_pointer(keyword));
+        let value = if enabled { json!(true) } else { json!(null) };
+        let patches = email_ids
+            .iter()
+            .map(|id| (id.clone(), json!({ &path: value })))
+            .collect();
+        self.update_emails(patches).await
+    }
+
+    /// Move emails from one mailbox to another, leaving any other mailbox
+    /// memberships untouched.
+    pub async fn move_emails(
+        &self,
+        email_ids: &[String],
+        from_mailbox_id: &str,
+        to_mailbox_id: &str,
+    ) -> Result<(), JmapError> {
+        let from_path = format!("mailboxIds/{}", escape_pointer(from_mailbox_id));
+        let to_path = format!("mailboxIds/{}", escape_pointer(to_mailbox_id));
+        let patches = email_ids
+            .iter()
+            .map(|id| (id.clone(), json!({ &from_path: null, &to_path: true })))
+            .collect();
+        self.update_emails(patches).await
+    }
+
+    /// Move emails out of every mailbox they're in and into `to_mailbox_id`.
+    /// `emails` must have their `mailboxIds` fetched.
+    pub async fn move_emails_from_all(
+        &self,
+        emails: &[Email],
+        to_mailbox_id: &str,
+    ) -> Result<(), JmapError> {
+        let to_path = format!("mailboxIds/{}", escape_pointer(to_mailbox_id));
+        let patches = emails
+            .iter()
+            .filter_map(|e| {
+                let mut patch = serde_json::Map::new();
+                for mailbox_id in e.mailbox_ids.iter().flat_map(|ids| ids.keys()) {
+                    if mailbox_id != to_mailbox_id {
+                        let path = format!("mailboxIds/{}", escape_pointer(mailbox_id));
+                        patch.insert(path, Value::Null);
+                    }
+                }
+                patch.insert(to_path.clone(), json!(true));
+                Some((e.id.clone()?, Value::Object(patch)))
+            })
+            .collect();
+        self.update_emails(patches).await
+    }
+
+    /// Permanently delete emails.
+    pub async fn destroy_emails(&self, email_ids: &[String]) -> Result<(), JmapError> {
+        if email_ids.is_empty() {
+            return Ok(());
+        }
+
+        let response = self
+            .api_request(vec![Invocation {
+                name: "Email/set".to_string(),
+                args: json!({
+                    "accountId": self.account_id,
+                    "destroy": email_ids,
+                }),
+                call_id: "ed0".to_string(),
+            }])
+            .await?;
+
+        check_set_errors(&response.method_responses[0].args, "notDestroyed")
+    }
+
+    /// Get the emails in the given threads, with their `id` and `mailboxIds`,
+    /// that are in a mailbox (or in any with `None`). Uses a back-reference
+    /// so it takes a single round trip.
+    pub async fn get_thread_emails(
+        &self,
+        thread_ids: &[String],
+        mailbox_id: Option<&str>,
+    ) -> Result<Vec<Email>, JmapError> {
+        if thread_ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut batch = self.batch();
+        let thread_call = batch.thread_get(thread_ids);
+        let email_ids = thread_call.reference("/list/*/emailIds");
+        let email_call = batch.email_get(email_ids, &["id", "mailboxIds"]);
+        let emails = batch.send().await?.get(&email_call)?.list;
+
+        Ok(emails
+            .into_iter()
+            .filter(|e| {
+                mailbox_id.is_none_or(|mailbox_id| {
+                    e.mailbox_ids
+                        .as_ref()
+                        .is_some_and(|ids| ids.contains_key(mailbox_id))
+                })
+            })
+            .collect())
+    }
+
+    /// Apply PatchObjects to emails via Email/set, keyed by email ID.
+    async fn update_emails(
+        &self,
+        patches: serde_json::Map<String, Value>,
+    ) -> Result<(), JmapError> {
+        if patches.is_empty() {
+            return Ok(());
+        }
+
+        let response = self
+            .api_request(vec![Invocation {
+                name: "Email/set".to_string(),
+                args: json!({
+                    "accountId": self.account_id,
+                    "update": patches,
+                }),
+                call_id: "eu0".to_string(),
+            }])
+            .await?;
+
+        check_set_errors(&response.method_responses[0].args, "notUpdated")
+    }
+
     /// Get mailbox changes since a given state.
     pub async fn get_mailbox_changes(
         &self,
@@ -426,6 +912,57 @@ impl JmapClient {
         Ok(changes)
     }
 
+    /// Bring `mailboxes` up to date from `since_state` with Mailbox/changes,
+    /// fetching only created and updated mailboxes (and only their counts
+    /// when nothing else changed). Returns the new state.
+    pub async fn sync_mailboxes(
+        &self,
+        mailboxes: &mut Vec<Mailbox>,
+        since_state: &str,
+    ) -> Result<String, JmapError> {
+        let mut state = since_state.to_string();
+        loop {
+            let mut batch = self.batch();
+            let changes_call = batch.mailbox_changes(&state);
+            let created_call = batch.mailbox_get(changes_call.reference("/created"));
+            // Objects may be partial, so merge them as JSON
+            let updated_call: Call<GetResponse<Value>> = batch.call(
+                "Mailbox/get",
+                json!({
+                    "#ids": changes_call.reference("/updated"),
+                    "#properties": changes_call.reference("/updatedProperties"),
+                }),
+            );
+            let response = batch.send().await?;
+            let changes = response.get(&changes_call)?;
+
+            for mailbox in response.get(&created_call)?.list {
+                mailboxes.retain(|m| m.id != mailbox.id);
+                mailboxes.push(mailbox);
+            }
+            for patch in response.get(&updated_call)?.list {
+                let Some(id) = patch["id"].as_str() else { continue };
+                match mailboxes.iter_mut().find(|m| m.id == id) {
+                    Some(existing) => {
+                        let mut merged = serde_json::to_value(&*existing)?;
+                        if let (Some(merged), Value::Object(patch)) = (merged.as_object_mut(), patch)
+                        {
+                            merged.extend(patch);
+                        }
+                        *existing = serde_json::from_value(merged)?;
+                    }
+                    None => mailboxes.push(serde_json::from_value(patch)?),
+                }
+            }
+            mailboxes.retain(|m| !changes.destroyed.contains(&m.id));
+
+            state = changes.new_state;
+            if !changes.has_more_changes {
+                return Ok(state);
+            }
+        }
+    }
+
     /// Get email changes since a given state.
     pub async fn get_email_changes(
         &self,
@@ -447,6 +984,142 @@ impl JmapClient {
         Ok(changes)
     }
 
+    /// Get EmailSubmissions by ID.
+    pub async fn get_email_submissions(
+        &self,
+        ids: &[String],
+    ) -> Result<GetResponse<EmailSubmission>, JmapError> {
+        let mut batch = self.batch();
+        let get_call = batch.email_submission_get(ids);
+        batch.send().await?.get(&get_call)
+    }
+
+    /// Query EmailSubmissions, newest first, and fetch them in the same
+    /// request. Returns the submissions and their /get state.
+    pub async fn query_email_submissions(
+        &self,
+        filter: &EmailSubmissionFilter,
+        limit: Option<u64>,
+    ) -> Result<(Vec<EmailSubmission>, String), JmapError> {
+        let mut batch = self.batch();
+        let query_call = batch.email_submission_query(filter, limit);
+        let get_call = batch.email_submission_get(query_call.reference("/ids"));
+        let response = batch.send().await?;
+        response.get(&query_call)?;
+        let submissions = response.get(&get_call)?;
+        Ok((submissions.list, submissions.state))
+    }
+
+    /// Get EmailSubmission changes since a given state.
+    pub async fn get_email_submission_changes(
+        &self,
+        since_state: &str,
+    ) -> Result<ChangesResponse, JmapError> {
+        let mut batch = self.batch();
+        let changes_call = batch.email_submission_changes(since_state);
+        batch.send().await?.get(&changes_call)
+    }
+
+    /// Parse the submission capability for the current account.
+    pub fn submission_capabilities(&self) -> Option<SubmissionCapabilities> {
+        self.session
+            .accounts
+            .get(&self.account_id)?
+            .account_capabilities
+            .get("urn:ietf:params:jmap:submission")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+    }
+
+    /// Parse the mail capability for the current account.
+    pub fn mail_capabilities(&self) -> Option<MailCapabilities> {
+        self.session
+            .accounts
+            .get(&self.account_id)?
+            .account_capabilities
+            .get("urn:ietf:params:jmap:mail")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+    }
+
+    /// Upload a blob to the account, returning its blobId.
+    /// Fails without contacting the server if `data` exceeds `maxSizeUpload`.
+    pub async fn upload_blob(
+        &self,
+        content_type: &str,
+        data: Vec<u8>,
+    ) -> Result<BlobUpload, JmapError> {
+        let size = data.len() as u64;
+        if let Some(core) = self.session.core_capabilities()
+            && core.max_size_upload > 0
+            && size > core.max_size_upload
+        {
+            return Err(JmapError::UploadTooLarge {
+                size,
+                max: core.max_size_upload,
+            });
+        }
+
+        let url = self.upload_url();
+
+        let response = self
+            .http
+            .post(&url)
+            .header(AUTHORIZATION, &self.auth_header)
+            .header(CONTENT_TYPE, content_type)
+            .body(data)
+            .send()
+            .await?;
+
+        if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+            return Err(JmapError::Auth);
+        }
+
+        let upload: BlobUpload = response.error_for_status()?.json().await?;
+        Ok(upload)
+    }
+
+    /// The upload URL for the current account, for callers that need to
+    /// perform the upload themselves (e.g. to report progress).
+    pub fn upload_url(&self) -> String {
+        self.session
+            .upload_url
+            .replace("{accountId}", &percent_encode(&self.account_id))
+    }
+
+    /// Build the download URL for a blob by expanding the session's
+    /// `downloadUrl` template. The URL still requires the Authorization header.
+    pub fn download_url(&self, blob_id: &str, content_type: &str, name: &str) -> String {
+        self.session
+            .download_url
+            .replace("{accountId}", &percent_encode(&self.account_id))
+            .replace("{blobId}", &percent_encode(blob_id))
+            .replace("{type}", &percent_encode(content_type))
+            .replace("{name}", &percent_encode(name))
+    }
+
+    /// Download a blob's raw bytes.
+    pub async fn download_blob(
+        &self,
+        blob_id: &str,
+        content_type: &str,
+        name: &str,
+    ) -> Result<Vec<u8>, JmapError> {
+        let url = self.download_url(blob_id, content_type, name);
+
+        let response = self
+            .http
+            .get(&url)
+            .header(AUTHORIZATION, &self.auth_header)
+            .send()
+            .await?;
+
+        if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+            return Err(JmapError::Auth);
+        }
+
+        let bytes = response.error_for_status()?.bytes().await?;
+        Ok(bytes.to_vec())
+    }
+
     /// Find a mailbox by role (e.g. "drafts", "sent", "inbox").
     pub fn find_mailbox_by_role<'a>(
         &self,
@@ -459,6 +1132,117 @@ impl JmapClient {
     }
 }
 
+/// Convert an `error` method response into a MethodError.
+pub(crate) fn method_error(inv: &Invocation) -> JmapError {
+    let type_ = inv.args["type"]
+        .as_str()
+        .unwrap_or("unknown")
+        .to_string();
+    let description = inv.args["description"].as_str().map(|s| s.to_string());
+    JmapError::MethodError { type_, description }
+}
+
+/// Return the first SetError in a /set response's `notCreated`,
+/// `notUpdated` or `notDestroyed` map as a MethodError.
+fn check_set_errors(args: &Value, field: &str) -> Result<(), JmapError> {
+    if let Some(err) = args[field].as_object().and_then(|errors| errors.values().next()) {
+        let type_ = err["type"].as_str().unwrap_or("unknown").to_string();
+        let description = err["description"].as_str().map(|s| s.to_string());
+        return Err(JmapError::MethodError { type_, description });
+    }
+    Ok(())
+}
+
+/// Escape a JSON Pointer path segment (RFC 6901) for use in a PatchObject.
+fn escape_pointer(segment: &str) -> String {
+    segment.replace('~', "~0").replace('/', "~1")
+}
+
+/// An envelope holding the submission for later release, or `None` to let
+/// the server derive the envelope and send immediately.
+fn held_envelope(email: &OutgoingEmail) -> Option<Envelope> {
+    let (name, value) = email.release.as_ref()?.parameter();
+    let mail_from = SubmissionAddress {
+        email: email.from.first()?.email.clone(),
+        parameters: Some([(name, Some(value))].into_iter().collect()),
+    };
+    let rcpt_to = email
+        .to
+        .iter()
+        .chain(&email.cc)
+        .chain(&email.bcc)
+        .map(|a| SubmissionAddress::new(&a.email))
+        .collect();
+    Some(Envelope { mail_from, rcpt_to })
+}
+
+/// The Email/set create object for `email`, as a seen draft in `mailbox_id`.
+fn email_create_args(email: &OutgoingEmail, mailbox_id: &str) -> Value {
+    let mut mailbox_ids = serde_json::Map::new();
+    mailbox_ids.insert(mailbox_id.to_string(), json!(true));
+
+    let mut email_create = json!({
+        "mailboxIds": mailbox_ids,
+        "from": email.from,
+        "to": email.to,
+        "subject": email.subject,
+        "keywords": { "$seen": true, "$draft": true },
+        "textBody": [{
+            "partId": "body",
+            "type": "text/plain",
+        }],
+        "bodyValues": {
+            "body": {
+                "value": email.text_body,
+            },
+        },
+    });
+
+    if !email.cc.is_empty() {
+        email_create["cc"] = json!(email.cc);
+    }
+    if !email.bcc.is_empty() {
+        email_create["bcc"] = json!(email.bcc);
+    }
+    if !email.reply_to.is_empty() {
+        email_create["replyTo"] = json!(email.reply_to);
+    }
+    if let Some(message_id) = &email.message_id {
+        email_create["messageId"] = json!([message_id]);
+    }
+    if !email.in_reply_to.is_empty() {
+        email_create["inReplyTo"] = json!(email.in_reply_to);
+    }
+    if !email.references.is_empty() {
+        email_create["references"] = json!(email.references);
+    }
+    if !email.attachments.is_empty() {
+        // With attachments present the server builds a multipart/mixed
+        // message around the text body (RFC 8621 §4.6).
+        email_create["attachments"] = email
+            .attachments
+            .iter()
+            .map(attachment_part)
+            .collect::<Vec<_>>()
+            .into();
+    }
+    email_create
+}
+
+/// Build an EmailBodyPart for Email/set creation that references an uploaded
+/// blob. `partId`, `charset` and `size` must not be sent alongside a blobId.
+fn attachment_part(part: &EmailBodyPart) -> Value {
+    let mut value = json!({
+        "blobId": part.blob_id,
+        "type": part.type_.as_deref().unwrap_or("application/octet-stream"),
+        "disposition": part.disposition.as_deref().unwrap_or("attachment"),
+    });
+    if let Some(name) = &part.name {
+        value["name"] = json!(name);
+    }
+    value
+}
+
 /// Simple base64 encoding for Basic auth (no external dependency needed).
 fn base64_encode(input: &[u8]) -> String {
     const CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
@@ -483,3 +1267,59 @@ fn base64_encode(input: &[u8]) -> String {
     }
     result
 }
+
+/// Percent-encode a value for substitution into a URL template (RFC 6570
+/// simple expansion: everything except unreserved characters is encoded).
+fn percent_encode(input: &str) -> String {
+    let mut result = String::with_capacity(input.len());
+    for b in input.bytes() {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~') {
+            result.push(b as char);
+        } else {
+            result.push_str(&format!("%{b:02X}"));
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_pointer_segments() {
+        assert_eq!(escape_pointer("inbox"), "inbox");
+        assert_eq!(escape_pointer("a/b"), "a~1b");
+        assert_eq!(escape_pointer("a~b"), "a~0b");
+        // `~` is escaped first so the `~1` from a `/` isn't escaped again
+        assert_eq!(escape_pointer("~/"), "~0~1");
+    }
+
+    #[test]
+    fn held_envelope_only_for_releases() {
+        let mut email = OutgoingEmail {
+            from: vec![EmailAddress {
+                name: None,
+                email: "me@example.com".to_string(),
+            }],
+            to: vec![EmailAddress {
+                name: None,
+                email: "you@example.com".to_string(),
+            }],
+            bcc: vec![EmailAddress {
+                name: None,
+                email: "hidden@example.com".to_string(),
+            }],
+            ..Default::default()
+        };
+        assert!(held_envelope(&email).is_none());
+
+        email.release = Some(Release::After(10));
+        let envelope = held_envelope(&email).unwrap();
+        assert_eq!(envelope.mail_from.email, "me@example.com");
+        let parameters = envelope.mail_from.parameters.unwrap();
+        assert_eq!(parameters["HOLDFOR"].as_deref(), Some("10"));
+        let rcpt_to: Vec<&str> = envelope.rcpt_to.iter().map(|a| a.email.as_str()).collect();
+        assert_eq!(rcpt_to, ["you@example.com", "hidden@example.com"]);
+    }
+}
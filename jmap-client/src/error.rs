@@ -23,9 +23,26 @@ pub enum JmapError {
         description: Option<String>,
     },
 
+    #[error("Upload of {size} bytes exceeds server limit of {max} bytes")]
+    UploadTooLarge { size: u64, max: u64 },
+
+    #[error("Attachments total {size} bytes, exceeding server limit of {max} bytes")]
+    AttachmentsTooLarge { size: u64, max: u64 },
+
     #[error("JSON error: {0}")]
     Json(#[from] serde_json::Error),
 
     #[error("EventSource error: {0}")]
     EventSource(String),
 }
+
+impl JmapError {
+    /// True if the request never got a response, e.g. the network is down or
+    /// the server is unreachable. HTTP status and decode errors aren't.
+    pub fn is_network(&self) -> bool {
+        matches!(
+            self,
+            JmapError::Http(e) if e.status().is_none() && !e.is_decode() && !e.is_builder()
+        )
+    }
+}
@@ -1,7 +1,9 @@
+pub mod batch;
 pub mod client;
 pub mod error;
 pub mod types;
 
+pub use batch::{BatchResponse, Call, Ids, RequestBuilder};
 pub use client::JmapClient;
 pub use error::JmapError;
 pub use types::*;
@@ -18,6 +18,79 @@ pub struct Session {
     pub state: String,
 }
 
+impl Session {
+    /// Parse the `urn:ietf:params:jmap:core` capability object.
+    pub fn core_capabilities(&self) -> Option<CoreCapabilities> {
+        self.capabilities
+            .get("urn:ietf:params:jmap:core")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+    }
+}
+
+/// Server limits advertised in the core capability (RFC 8620 §2).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CoreCapabilities {
+    #[serde(default)]
+    pub max_size_upload: u64,
+    #[serde(default)]
+    pub max_concurrent_upload: u64,
+    #[serde(default)]
+    pub max_size_request: u64,
+    #[serde(default)]
+    pub max_concurrent_requests: u64,
+    #[serde(default)]
+    pub max_calls_in_request: u64,
+    #[serde(default)]
+    pub max_objects_in_get: u64,
+    #[serde(default)]
+    pub max_objects_in_set: u64,
+    #[serde(default)]
+    pub collation_algorithms: Vec<String>,
+}
+
+/// Account limits advertised in the mail capability (RFC 8621 §1.3.1).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MailCapabilities {
+    #[serde(default)]
+    pub max_mailboxes_per_email: Option<u64>,
+    #[serde(default)]
+    pub max_mailbox_depth: Option<u64>,
+    #[serde(default)]
+    pub max_size_mailbox_name: u64,
+    #[serde(default)]
+    pub max_size_attachments_per_email: u64,
+    #[serde(default)]
+    pub email_query_sort_options: Vec<String>,
+    #[serde(default)]
+    pub may_create_top_level_mailbox: bool,
+}
+
+/// Account limits advertised in the submission capability (RFC 8621 §1.3.2).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubmissionCapabilities {
+    /// Longest a submission may be held before release, in seconds; 0 if
+    /// delayed sending isn't supported.
+    #[serde(default)]
+    pub max_delayed_send: u64,
+    /// SMTP extensions (EHLO keyword → arguments) the server supports.
+    #[serde(default)]
+    pub submission_extensions: HashMap<String, Vec<String>>,
+}
+
+impl SubmissionCapabilities {
+    /// Whether submissions can be held for later release (RFC 4865).
+    pub fn supports_future_release(&self) -> bool {
+        self.max_delayed_send > 0
+            && self
+                .submission_extensions
+                .keys()
+                .any(|k| k.eq_ignore_ascii_case("FUTURERELEASE"))
+    }
+}
+
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Account {
@@ -96,6 +169,12 @@ pub struct Email {
     #[serde(default)]
     pub received_at: Option<String>,
     #[serde(default)]
+    pub message_id: Option<Vec<String>>,
+    #[serde(default)]
+    pub in_reply_to: Option<Vec<String>>,
+    #[serde(default)]
+    pub references: Option<Vec<String>>,
+    #[serde(default)]
     pub from: Option<Vec<EmailAddress>>,
     #[serde(default)]
     pub to: Option<Vec<EmailAddress>>,
@@ -118,9 +197,31 @@ pub struct Email {
     #[serde(default)]
     pub html_body: Option<Vec<EmailBodyPart>>,
     #[serde(default)]
+    pub attachments: Option<Vec<EmailBodyPart>>,
+    #[serde(default)]
     pub body_values: Option<HashMap<String, EmailBodyValue>>,
 }
 
+impl Email {
+    /// Whether a keyword such as "$seen" or "$flagged" is set.
+    pub fn has_keyword(&self, keyword: &str) -> bool {
+        self.keywords
+            .as_ref()
+            .is_some_and(|kw| kw.get(keyword).copied().unwrap_or(false))
+    }
+
+    /// Set or clear a keyword locally, e.g. to reflect a change made with
+    /// `JmapClient::set_keyword` without refetching.
+    pub fn set_keyword(&mut self, keyword: &str, enabled: bool) {
+        let keywords = self.keywords.get_or_insert_with(HashMap::new);
+        if enabled {
+            keywords.insert(keyword.to_string(), true);
+        } else {
+            keywords.remove(keyword);
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct EmailAddress {
@@ -155,6 +256,8 @@ pub struct EmailBodyPart {
     pub charset: Option<String>,
     #[serde(default)]
     pub disposition: Option<String>,
+    #[serde(default)]
+    pub cid: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -167,6 +270,133 @@ pub struct EmailBodyValue {
     pub is_truncated: Option<bool>,
 }
 
+/// A message to be created and submitted by `JmapClient::send_email`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutgoingEmail {
+    /// Message-ID (without angle brackets) to give the message instead of
+    /// letting the server generate one. Lets a retried send be recognised.
+    #[serde(default)]
+    pub message_id: Option<String>,
+    /// A saved draft of this message, destroyed once it's created.
+    #[serde(default)]
+    pub replaces_draft: Option<String>,
+    pub from: Vec<EmailAddress>,
+    pub to: Vec<EmailAddress>,
+    pub cc: Vec<EmailAddress>,
+    pub bcc: Vec<EmailAddress>,
+    #[serde(default)]
+    pub reply_to: Vec<EmailAddress>,
+    pub subject: String,
+    pub text_body: String,
+    /// Message-IDs (without angle brackets) of the message being replied to.
+    pub in_reply_to: Vec<String>,
+    pub references: Vec<String>,
+    /// Uploaded blobs to attach. Only `blob_id`, `type_`, `name` and
+    /// `disposition` are sent; the server computes the size.
+    pub attachments: Vec<EmailBodyPart>,
+    /// Emails this message forwards, given the `$forwarded` keyword once
+    /// it has been submitted. Not set for held messages, whose submission
+    /// may yet be canceled.
+    #[serde(default)]
+    pub forwarded: Vec<String>,
+    /// Hold the submission instead of sending straight away. Requires
+    /// FUTURERELEASE; see [`SubmissionCapabilities::supports_future_release`].
+    #[serde(default)]
+    pub release: Option<Release>,
+}
+
+/// When a held submission is released for delivery (RFC 4865).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Release {
+    /// After this many seconds (`HOLDFOR`).
+    After(u64),
+    /// At this UTC date-time, e.g. `2024-01-01T09:00:00Z` (`HOLDUNTIL`).
+    At(String),
+}
+
+impl Release {
+    /// The `mailFrom` envelope parameter requesting this release.
+    pub fn parameter(&self) -> (String, String) {
+        match self {
+            Release::After(seconds) => ("HOLDFOR".to_string(), seconds.to_string()),
+            Release::At(date) => ("HOLDUNTIL".to_string(), date.clone()),
+        }
+    }
+}
+
+/// An email submitted for delivery (RFC 8621 §7).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmailSubmission {
+    pub id: String,
+    pub identity_id: String,
+    pub email_id: String,
+    #[serde(default)]
+    pub thread_id: Option<String>,
+    #[serde(default)]
+    pub envelope: Option<Envelope>,
+    /// When the message was, or will be, released for delivery.
+    #[serde(default)]
+    pub send_at: Option<String>,
+    pub undo_status: UndoStatus,
+    /// Per-recipient status, keyed by envelope `rcptTo` address.
+    #[serde(default)]
+    pub delivery_status: Option<HashMap<String, DeliveryStatus>>,
+    #[serde(default)]
+    pub dsn_blob_ids: Vec<String>,
+    #[serde(default)]
+    pub mdn_blob_ids: Vec<String>,
+}
+
+/// Whether a submission can still be canceled.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum UndoStatus {
+    /// Held, so it may be canceled.
+    Pending,
+    /// Sent; too late to cancel.
+    Final,
+    Canceled,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeliveryStatus {
+    /// The SMTP reply from the last attempt, e.g. `250 2.0.0 OK`.
+    pub smtp_reply: String,
+    pub delivered: Delivered,
+    #[serde(default)]
+    pub displayed: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Delivered {
+    /// Still trying to deliver.
+    Queued,
+    Yes,
+    /// Failed permanently.
+    No,
+    Unknown,
+}
+
+/// What `JmapClient::send_email` created.
+#[derive(Debug, Clone)]
+pub struct SentEmail {
+    pub email_id: String,
+    pub submission_id: String,
+}
+
+impl OutgoingEmail {
+    /// Sum of the attachment sizes, as compared against
+    /// `maxSizeAttachmentsPerEmail`.
+    pub fn attachments_size(&self) -> u64 {
+        self.attachments.iter().filter_map(|a| a.size).sum()
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Identity {
@@ -186,6 +416,30 @@ pub struct Identity {
     pub may_delete: Option<bool>,
 }
 
+/// The editable properties of an identity, as sent to Identity/set. The
+/// address itself can only be chosen when creating one.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IdentityProperties {
+    pub name: String,
+    pub reply_to: Option<Vec<EmailAddress>>,
+    pub bcc: Option<Vec<EmailAddress>>,
+    pub text_signature: String,
+    pub html_signature: String,
+}
+
+impl From<&Identity> for IdentityProperties {
+    fn from(identity: &Identity) -> Self {
+        IdentityProperties {
+            name: identity.name.clone().unwrap_or_default(),
+            reply_to: identity.reply_to.clone(),
+            bcc: identity.bcc.clone(),
+            text_signature: identity.text_signature.clone().unwrap_or_default(),
+            html_signature: identity.html_signature.clone().unwrap_or_default(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Thread {
@@ -193,6 +447,318 @@ pub struct Thread {
     pub email_ids: Vec<String>,
 }
 
+// ── Submission Types ──
+
+/// SMTP envelope for an EmailSubmission (RFC 8621 §7). When omitted the
+/// server derives it from the message headers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Envelope {
+    pub mail_from: SubmissionAddress,
+    pub rcpt_to: Vec<SubmissionAddress>,
+}
+
+/// An envelope address with optional SMTP extension parameters, such as
+/// `HOLDFOR` for FUTURERELEASE.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubmissionAddress {
+    pub email: String,
+    #[serde(default)]
+    pub parameters: Option<HashMap<String, Option<String>>>,
+}
+
+impl SubmissionAddress {
+    pub fn new(email: impl Into<String>) -> Self {
+        SubmissionAddress {
+            email: email.into(),
+            parameters: None,
+        }
+    }
+}
+
+// ── Query Types ──
+
+/// An Email/query filter (RFC 8621 §4.4.1): either a single condition or an
+/// operator combining nested filters.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum EmailFilter {
+    Operator(FilterOperator),
+    Condition(Box<EmailFilterCondition>),
+}
+
+impl EmailFilter {
+    /// Emails in the given mailbox.
+    pub fn in_mailbox(mailbox_id: &str) -> Self {
+        EmailFilterCondition {
+            in_mailbox: Some(mailbox_id.to_string()),
+            ..Default::default()
+        }
+        .into()
+    }
+
+    /// All of `filters` must match. A single filter is returned unwrapped.
+    pub fn and(mut filters: Vec<EmailFilter>) -> Self {
+        if filters.len() == 1 {
+            return filters.remove(0);
+        }
+        EmailFilter::Operator(FilterOperator {
+            operator: Operator::And,
+            conditions: filters,
+        })
+    }
+
+    /// At least one of `filters` must match.
+    pub fn or(filters: Vec<EmailFilter>) -> Self {
+        EmailFilter::Operator(FilterOperator {
+            operator: Operator::Or,
+            conditions: filters,
+        })
+    }
+
+    /// `filter` must not match.
+    pub fn negate(filter: EmailFilter) -> Self {
+        EmailFilter::Operator(FilterOperator {
+            operator: Operator::Not,
+            conditions: vec![filter],
+        })
+    }
+}
+
+impl From<EmailFilterCondition> for EmailFilter {
+    fn from(condition: EmailFilterCondition) -> Self {
+        EmailFilter::Condition(Box::new(condition))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FilterOperator {
+    pub operator: Operator,
+    pub conditions: Vec<EmailFilter>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Operator {
+    And,
+    Or,
+    Not,
+}
+
+/// A FilterCondition for Email/query. Every property set must match.
+/// Dates are UTCDate strings (e.g. "2024-01-31T00:00:00Z").
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmailFilterCondition {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub in_mailbox: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub in_mailbox_other_than: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub all_in_thread_have_keyword: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub some_in_thread_have_keyword: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub none_in_thread_have_keyword: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub has_keyword: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub not_keyword: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub has_attachment: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cc: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bcc: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    /// Header name, optionally followed by a value the header must contain.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub header: Option<Vec<String>>,
+}
+
+/// A property to sort Email/query results by (RFC 8621 §4.4.2). The
+/// keyword variants carry the keyword to test.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EmailSortProperty {
+    ReceivedAt,
+    SentAt,
+    Size,
+    From,
+    To,
+    Subject,
+    HasKeyword(String),
+    AllInThreadHaveKeyword(String),
+    SomeInThreadHaveKeyword(String),
+}
+
+impl EmailSortProperty {
+    fn name(&self) -> &'static str {
+        match self {
+            EmailSortProperty::ReceivedAt => "receivedAt",
+            EmailSortProperty::SentAt => "sentAt",
+            EmailSortProperty::Size => "size",
+            EmailSortProperty::From => "from",
+            EmailSortProperty::To => "to",
+            EmailSortProperty::Subject => "subject",
+            EmailSortProperty::HasKeyword(_) => "hasKeyword",
+            EmailSortProperty::AllInThreadHaveKeyword(_) => "allInThreadHaveKeyword",
+            EmailSortProperty::SomeInThreadHaveKeyword(_) => "someInThreadHaveKeyword",
+        }
+    }
+
+    fn keyword(&self) -> Option<&str> {
+        match self {
+            EmailSortProperty::HasKeyword(k)
+            | EmailSortProperty::AllInThreadHaveKeyword(k)
+            | EmailSortProperty::SomeInThreadHaveKeyword(k) => Some(k),
+            _ => None,
+        }
+    }
+}
+
+/// A Comparator for Email/query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmailSort {
+    pub property: EmailSortProperty,
+    pub is_ascending: bool,
+    /// Collation algorithm for string properties, e.g. "i;unicode-casemap".
+    pub collation: Option<String>,
+}
+
+impl EmailSort {
+    pub fn ascending(property: EmailSortProperty) -> Self {
+        EmailSort {
+            property,
+            is_ascending: true,
+            collation: None,
+        }
+    }
+
+    pub fn descending(property: EmailSortProperty) -> Self {
+        EmailSort {
+            property,
+            is_ascending: false,
+            collation: None,
+        }
+    }
+}
+
+impl Serialize for EmailSort {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("property", self.property.name())?;
+        map.serialize_entry("isAscending", &self.is_ascending)?;
+        if let Some(keyword) = self.property.keyword() {
+            map.serialize_entry("keyword", keyword)?;
+        }
+        if let Some(collation) = &self.collation {
+            map.serialize_entry("collation", collation)?;
+        }
+        map.end()
+    }
+}
+
+/// Arguments for Email/query (RFC 8620 §5.5, RFC 8621 §4.4). Unset optional
+/// fields are omitted; `collapseThreads`, `position` and `calculateTotal`
+/// are always sent, defaulting to the RFC's own defaults (false, 0, false).
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmailQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter: Option<EmailFilter>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub sort: Vec<EmailSort>,
+    /// Return only the first matching email per thread.
+    pub collapse_threads: bool,
+    /// Zero-based index of the first result; negative counts from the end.
+    pub position: i64,
+    /// Email ID to start from instead of `position`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anchor: Option<String>,
+    /// Offset from the anchor's index; ignored without an anchor.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anchor_offset: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u64>,
+    pub calculate_total: bool,
+}
+
+/// Response to Email/query.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmailQueryResponse {
+    pub ids: Vec<String>,
+    /// Index of the first returned ID, which can differ from the requested
+    /// position when an anchor was used.
+    pub position: u64,
+    /// Only present if `calculateTotal` was requested.
+    pub total: Option<u64>,
+    pub query_state: String,
+    pub can_calculate_changes: bool,
+}
+
+/// Filter for EmailSubmission/query. Unset conditions match everything.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmailSubmissionFilter {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub identity_ids: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_ids: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thread_ids: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub undo_status: Option<UndoStatus>,
+    /// UTC date-time the submission's `sendAt` must be before.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<String>,
+}
+
+/// Response to a /query method other than Email/query.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryResponse {
+    pub ids: Vec<String>,
+    pub position: u64,
+    #[serde(default)]
+    pub total: Option<u64>,
+    pub query_state: String,
+    pub can_calculate_changes: bool,
+}
+
+/// Response to a /get method. SearchSnippet/get has no `state`, and its
+/// `notFound` may be null.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetResponse<T> {
+    #[serde(default)]
+    pub state: String,
+    pub list: Vec<T>,
+    #[serde(default)]
+    pub not_found: Option<Vec<String>>,
+}
+
 // ── Protocol Types ──
 
 #[derive(Debug, Clone, Serialize)]
@@ -230,6 +796,37 @@ impl<'de> Deserialize<'de> for Invocation {
     }
 }
 
+/// Response to Email/queryChanges (RFC 8620 §5.6). Apply `removed`, then
+/// insert `added` in index order, to bring a cached result list up to date.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmailQueryChangesResponse {
+    pub old_query_state: String,
+    pub new_query_state: String,
+    pub total: Option<u64>,
+    #[serde(default)]
+    pub removed: Vec<String>,
+    #[serde(default)]
+    pub added: Vec<AddedItem>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddedItem {
+    pub id: String,
+    pub index: u64,
+}
+
+/// Highlighted matches for one email from SearchSnippet/get (RFC 8621 §5).
+/// `subject` and `preview` are HTML with matches wrapped in `<mark>`, or
+/// `None` if that part didn't match.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchSnippet {
+    pub email_id: String,
+    pub subject: Option<String>,
+    pub preview: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ResultReference {
@@ -238,6 +835,17 @@ pub struct ResultReference {
     pub path: String,
 }
 
+/// Response to a blob upload (RFC 8620 §6.1).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlobUpload {
+    pub account_id: String,
+    pub blob_id: String,
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub size: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SetError {
@@ -269,4 +877,86 @@ pub struct ChangesResponse {
     pub updated: Vec<String>,
     #[serde(default)]
     pub destroyed: Vec<String>,
+    /// Mailbox/changes only: set when nothing but the counts changed.
+    #[serde(default)]
+    pub updated_properties: Option<Vec<String>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn get_response_not_found_list() {
+        let response: GetResponse<SearchSnippet> = serde_json::from_value(json!({
+            "accountId": "a",
+            "list": [],
+            "notFound": ["e1"],
+        }))
+        .unwrap();
+        assert_eq!(response.not_found, Some(vec!["e1".to_string()]));
+    }
+
+    #[test]
+    fn get_response_not_found_null() {
+        let response: GetResponse<SearchSnippet> = serde_json::from_value(json!({
+            "accountId": "a",
+            "list": [{ "emailId": "e1", "subject": "<mark>hi</mark>", "preview": null }],
+            "notFound": null,
+        }))
+        .unwrap();
+        assert_eq!(response.list.len(), 1);
+        assert_eq!(response.not_found, None);
+    }
+
+    #[test]
+    fn email_query_omits_unset_options() {
+        let value = serde_json::to_value(EmailQuery::default()).unwrap();
+        assert_eq!(
+            value,
+            json!({ "collapseThreads": false, "position": 0, "calculateTotal": false })
+        );
+    }
+
+    #[test]
+    fn release_parameter() {
+        assert_eq!(
+            Release::After(30).parameter(),
+            ("HOLDFOR".to_string(), "30".to_string())
+        );
+        assert_eq!(
+            Release::At("2024-01-01T09:00:00Z".to_string()).parameter(),
+            ("HOLDUNTIL".to_string(), "2024-01-01T09:00:00Z".to_string())
+        );
+    }
+
+    #[test]
+    fn future_release_needs_extension_and_delay() {
+        let capabilities = |value| -> SubmissionCapabilities {
+            serde_json::from_value(value).unwrap()
+        };
+        assert!(
+            capabilities(json!({
+                "maxDelayedSend": 86400,
+                "submissionExtensions": { "futurerelease": ["86400"] },
+            }))
+            .supports_future_release()
+        );
+        assert!(
+            !capabilities(json!({
+                "maxDelayedSend": 0,
+                "submissionExtensions": { "FUTURERELEASE": [] },
+            }))
+            .supports_future_release()
+        );
+        assert!(
+            !capabilities(json!({
+                "maxDelayedSend": 86400,
+                "submissionExtensions": { "DSN": [] },
+            }))
+            .supports_future_release()
+        );
+        assert!(!capabilities(json!({})).supports_future_release());
+    }
 }
@@ -1,43 +1,78 @@
-use crate::components::{compose::ComposeView, email_list::EmailList, thread_view::ThreadView};
+use crate::components::{
+    compose::ComposeView, email_list::EmailList, identities::IdentitySettings, outbox::OutboxView,
+    sending::SendingView, thread_view::ThreadView,
+};
 use crate::pages::{login::LoginPage, mail::MailLayout};
+use crate::cache::{self, CachedMailboxes};
+use crate::outbox::{flush_outbox, mark_released_forwards};
 use crate::state::{load_saved_credentials, AppState};
-use jmap_client::JmapClient;
+use crate::store::{sync_store, StoreSnapshot};
+use crate::sync::sync_mailboxes;
+use jmap_client::{JmapClient, JmapError};
 use leptos::prelude::*;
 use leptos::task::spawn_local;
 use leptos_router::components::{ParentRoute, Redirect, Route, Router, Routes};
 use leptos_router::path;
+use std::time::Duration;
+
+/// How often queued mail is retried while connected.
+const OUTBOX_RETRY_INTERVAL: Duration = Duration::from_secs(60);
 
 #[component]
 pub fn App() -> impl IntoView {
     let state = AppState::new();
     provide_context(state);
 
-    // Try auto-login from saved credentials
-    if let Some((server, username, password)) = load_saved_credentials() {
-        spawn_local(async move {
-            if let Ok(client) = JmapClient::connect(&server, &username, &password).await {
-                let (mailboxes, mailbox_state) =
-                    client.get_mailboxes().await.ok().unwrap_or_default();
-                let identities = client.get_identities().await.ok().unwrap_or_default();
-
-                state.mailboxes.set(mailboxes);
-                state.mailbox_state.set(Some(mailbox_state));
-                state.identities.set(identities);
-                state.client.set(Some(client));
+    persist_to_cache(state);
 
-                crate::eventsource::start_event_source(state);
-            }
-            state.auto_login_done.set(true);
+    // Show cached mail straight away, then connect with saved credentials
+    if let Some(credentials) = load_saved_credentials() {
+        spawn_local(async move {
+            restore_from_cache(state).await;
+            connect_saved(state, credentials).await;
         });
     } else {
         state.auto_login_done.set(true);
     }
 
+    // Reconnect or send queued mail when the browser comes back online
+    let _ = window_event_listener(leptos::ev::online, move |_| {
+        if state.client.get_untracked().is_some() {
+            spawn_local(flush_outbox(state));
+        } else if state.offline.get_untracked()
+            && let Some(credentials) = load_saved_credentials()
+        {
+            spawn_local(connect_saved(state, credentials));
+        }
+    });
+
+    // The server can be down while the browser stays online, so also retry
+    // queued mail on a timer
+    let _ = set_interval_with_handle(
+        move || {
+            if state.client.get_untracked().is_some()
+                && !state.outbox.with_untracked(Vec::is_empty)
+            {
+                spawn_local(flush_outbox(state));
+            }
+        },
+        OUTBOX_RETRY_INTERVAL,
+    );
+
     view! {
         <Router base=option_env!("BASE_URL").unwrap_or("")>
             <Routes fallback=|| view! { <Redirect path="/login"/> }>
                 <Route path=path!("/") view=|| view! { <Redirect path="/mail/inbox"/> }/>
                 <Route path=path!("/login") view=LoginPage/>
+                <ParentRoute path=path!("/outbox") view=MailLayout>
+                    <Route path=path!("") view=OutboxView/>
+                </ParentRoute>
+                <ParentRoute path=path!("/sending") view=MailLayout>
+                    <Route path=path!("") view=SendingView/>
+                </ParentRoute>
+                <ParentRoute path=path!("/settings") view=MailLayout>
+                    <Route path=path!("") view=IdentitySettings/>
+                </ParentRoute>
                 <ParentRoute path=path!("/mail/:mailbox") view=MailLayout>
                     <Route path=path!("") view=EmailList/>
                     <Route path=path!("/compose") view=ComposeView/>
@@ -47,3 +82,101 @@ pub fn App() -> impl IntoView {
         </Router>
     }
 }
+
+async fn restore_from_cache(state: AppState) {
+    if let Some(outbox) = cache::load(cache::OUTBOX_KEY).await {
+        state.outbox.set(outbox);
+    }
+    if let Some(held) = cache::load(cache::HELD_FORWARDS_KEY).await {
+        state.held_forwards.set(held);
+    }
+    let Some(mailboxes) = cache::load::<CachedMailboxes>(cache::MAILBOXES_KEY).await else {
+        return;
+    };
+    if let Some(identities) = cache::load(cache::IDENTITIES_KEY).await {
+        state.identities.set(identities);
+    }
+    if let Some(snapshot) = cache::load::<StoreSnapshot>(cache::STORE_KEY).await {
+        state.store.restore(snapshot);
+    }
+    state.mailboxes.set(mailboxes.list);
+    state.mailbox_state.set(Some(mailboxes.state));
+    state.offline.set(true);
+}
+
+/// Connect with saved credentials and resync anything restored from the
+/// cache via /changes. Stays offline on network errors if there's cached
+/// mail to show; bad credentials log out.
+async fn connect_saved(state: AppState, (server, username, password): (String, String, String)) {
+    match JmapClient::connect(&server, &username, &password).await {
+        Ok(client) => {
+            let (identities, identity_state) =
+                client.get_identities().await.ok().unwrap_or_default();
+            state.identities.set(identities);
+            state.identity_state.set(Some(identity_state));
+            state.client.set(Some(client));
+            state.offline.set(false);
+            sync_mailboxes(state).await;
+            spawn_local(sync_store(state));
+            spawn_local(flush_outbox(state));
+            spawn_local(mark_released_forwards(state));
+
+            crate::eventsource::start_event_source(state);
+        }
+        Err(JmapError::Auth) => state.logout(),
+        Err(_) => {}
+    }
+    state.auto_login_done.set(true);
+}
+
+/// Mirror mailboxes, identities and the entity store into IndexedDB.
+fn persist_to_cache(state: AppState) {
+    Effect::new(move || {
+        let mailboxes = state.mailboxes.get();
+        if let Some(mailbox_state) = state.mailbox_state.get() {
+            let record = CachedMailboxes {
+                state: mailbox_state,
+                list: mailboxes,
+            };
+            spawn_local(async move { cache::save(cache::MAILBOXES_KEY, &record).await });
+        }
+    });
+
+    Effect::new(move || {
+        let outbox = state.outbox.get();
+        spawn_local(async move { cache::save(cache::OUTBOX_KEY, &outbox).await });
+    });
+
+    Effect::new(move || {
+        let held = state.held_forwards.get();
+        spawn_local(async move { cache::save(cache::HELD_FORWARDS_KEY, &held).await });
+    });
+
+    Effect::new(move || {
+        let identities = state.identities.get();
+        if !identities.is_empty() {
+            spawn_local(async move { cache::save(cache::IDENTITIES_KEY, &identities).await });
+        }
+    });
+
+    // The store changes on every keyword toggle, so write it at most once
+    // a second
+    let pending = StoredValue::new(false);
+    Effect::new(move || {
+        state.store.track();
+        if pending.get_value() {
+            return;
+        }
+        pending.set_value(true);
+        set_timeout(
+            move || {
+                pending.set_value(false);
+                let snapshot = state.store.snapshot();
+                if snapshot.email_state.is_some() {
+                    spawn_local(async move { cache::save(cache::STORE_KEY, &snapshot).await });
+                }
+            },
+            Duration::from_secs(1),
+        );
+    });
+}
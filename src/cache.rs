@@ -0,0 +1,143 @@
+use jmap_client::{Email, Mailbox};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{IdbDatabase, IdbRequest, IdbTransactionMode};
+
+const DB_NAME: &str = "jmap_webmail";
+const DB_VERSION: u32 = 1;
+/// A single key-value object store; values are JSON strings.
+const STORE_NAME: &str = "cache";
+
+thread_local! {
+    /// The open database, shared by every read and write.
+    static DB: RefCell<Option<IdbDatabase>> = const { RefCell::new(None) };
+}
+
+/// Key for the mailbox list and its state.
+pub const MAILBOXES_KEY: &str = "mailboxes";
+/// Key for the account's identities.
+pub const IDENTITIES_KEY: &str = "identities";
+/// Key for messages queued in the outbox.
+pub const OUTBOX_KEY: &str = "outbox";
+/// Key for forwards waiting on a held submission.
+pub const HELD_FORWARDS_KEY: &str = "held_forwards";
+/// Key for the entity store snapshot (emails, threads and their states).
+pub const STORE_KEY: &str = "store";
+
+/// Key for the cached window of a mailbox's email list.
+pub fn list_key(mailbox_id: &str) -> String {
+    format!("list:{mailbox_id}")
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CachedMailboxes {
+    pub state: String,
+    pub list: Vec<Mailbox>,
+}
+
+/// The loaded part of a mailbox's email list and the query state it was
+/// current to, so Email/queryChanges can bring it up to date.
+#[derive(Serialize, Deserialize)]
+pub struct CachedList {
+    pub query_state: String,
+    pub emails: Vec<Email>,
+}
+
+/// Read a cached value. Missing keys, unreadable values and IndexedDB
+/// failures all come back as `None`.
+pub async fn load<T: DeserializeOwned>(key: &str) -> Option<T> {
+    let db = open().await.ok()?;
+    let store = db.transaction_with_str(STORE_NAME).ok()?.object_store(STORE_NAME).ok()?;
+    let request = store.get(&JsValue::from_str(key)).ok()?;
+    let value = request_result(&request).await.ok()?;
+    serde_json::from_str(&value.as_string()?).ok()
+}
+
+/// Write a value to the cache, ignoring failures (the cache is best-effort).
+pub async fn save<T: Serialize>(key: &str, value: &T) {
+    let Ok(json) = serde_json::to_string(value) else { return };
+    let result = async {
+        let db = open().await?;
+        let store = db
+            .transaction_with_str_and_mode(STORE_NAME, IdbTransactionMode::Readwrite)?
+            .object_store(STORE_NAME)?;
+        let request = store.put_with_key(&JsValue::from_str(&json), &JsValue::from_str(key))?;
+        request_result(&request).await
+    }
+    .await;
+    if let Err(e) = result {
+        web_sys::console::warn_1(&format!("Failed to write {key} to cache: {e:?}").into());
+    }
+}
+
+/// Remove everything from the cache (on logout).
+pub async fn clear() {
+    let result = async {
+        let db = open().await?;
+        let store = db
+            .transaction_with_str_and_mode(STORE_NAME, IdbTransactionMode::Readwrite)?
+            .object_store(STORE_NAME)?;
+        request_result(&store.clear()?).await
+    }
+    .await;
+    if let Err(e) = result {
+        web_sys::console::warn_1(&format!("Failed to clear cache: {e:?}").into());
+    }
+}
+
+/// The database connection, opened on first use and then reused.
+async fn open() -> Result<IdbDatabase, JsValue> {
+    if let Some(db) = DB.with_borrow(Clone::clone) {
+        return Ok(db);
+    }
+    let factory = web_sys::window()
+        .ok_or_else(|| JsValue::from_str("No window"))?
+        .indexed_db()?
+        .ok_or_else(|| JsValue::from_str("IndexedDB not available"))?;
+    let request = factory.open_with_u32(DB_NAME, DB_VERSION)?;
+
+    let upgrade_request = request.clone();
+    let on_upgrade = Closure::once_into_js(move |_: web_sys::Event| {
+        if let Ok(db) = upgrade_request.result() {
+            let db: IdbDatabase = db.unchecked_into();
+            let _ = db.create_object_store(STORE_NAME);
+        }
+    });
+    request.set_onupgradeneeded(Some(on_upgrade.unchecked_ref()));
+
+    let db: IdbDatabase = request_result(&request).await?.unchecked_into();
+    // Another call may have opened it meanwhile; keep a single connection
+    if let Some(existing) = DB.with_borrow(Clone::clone) {
+        db.close();
+        return Ok(existing);
+    }
+    DB.set(Some(db.clone()));
+    Ok(db)
+}
+
+/// Wait for an IndexedDB request to finish, returning its result.
+async fn request_result(request: &IdbRequest) -> Result<JsValue, JsValue> {
+    let promise = js_sys::Promise::new(&mut |resolve, reject| {
+        let success_request = request.clone();
+        let on_success = Closure::once_into_js(move |_: web_sys::Event| {
+            let result = success_request.result().unwrap_or(JsValue::UNDEFINED);
+            let _ = resolve.call1(&JsValue::UNDEFINED, &result);
+        });
+        let error_request = request.clone();
+        let on_error = Closure::once_into_js(move |_: web_sys::Event| {
+            let error = error_request
+                .error()
+                .ok()
+                .flatten()
+                .map(JsValue::from)
+                .unwrap_or_else(|| JsValue::from_str("IndexedDB request failed"));
+            let _ = reject.call1(&JsValue::UNDEFINED, &error);
+        });
+        request.set_onsuccess(Some(on_success.unchecked_ref()));
+        request.set_onerror(Some(on_error.unchecked_ref()));
+    });
+    JsFuture::from(promise).await
+}
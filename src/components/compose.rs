@@ -1,18 +1,25 @@
+use crate::format::format_size;
+use crate::outbox::{send_or_queue, SendOutcome, UndoableSend};
 use crate::router::mailbox_id_to_slug;
-use crate::state::AppState;
-use jmap_client::EmailAddress;
+use crate::state::{save_undo_send_seconds, sender_domain, AppState, ReplyMode};
+use crate::upload::upload_file;
+use jmap_client::{Email, EmailAddress, EmailBodyPart, Identity, OutgoingEmail, Release};
 use leptos::prelude::*;
 use leptos::task::spawn_local;
-use leptos_router::hooks::use_navigate;
+use leptos_router::hooks::{use_navigate, use_query_map};
+use std::time::Duration;
+use wasm_bindgen::JsValue;
 
-/// Full-pane compose view for new emails.
+/// Full-pane compose view for new emails, or for editing a saved draft
+/// given as `?draft=<email id>`.
 #[component]
 pub fn ComposeView() -> impl IntoView {
     let state = use_context::<AppState>().expect("AppState to be provided");
     let navigate = use_navigate();
-    let navigate2 = navigate.clone();
+    let query = use_query_map();
+    let draft_param = Memo::new(move |_| query.with(|q| q.get("draft")));
 
-    let on_cancel = move |_| {
+    let on_close = move || {
         let mailboxes = state.mailboxes.get();
         let slug = state
             .selected_mailbox
@@ -23,92 +30,244 @@ pub fn ComposeView() -> impl IntoView {
         navigate(&format!("/mail/{slug}"), Default::default());
     };
 
-    let on_sent = move || {
-        let mailboxes = state.mailboxes.get();
-        let slug = state
-            .selected_mailbox
-            .get()
-            .as_deref()
-            .map(|id| mailbox_id_to_slug(&mailboxes, id))
-            .unwrap_or_else(|| "inbox".to_string());
-        navigate2(&format!("/mail/{slug}"), Default::default());
-    };
+    // Load the draft being edited, if any; a draft that has since been
+    // replaced or sent opens as an empty message. The client is read
+    // untracked so connecting doesn't rebuild the form and lose edits.
+    let draft = LocalResource::new(move || {
+        let client = state.client.get_untracked();
+        let draft_id = draft_param.get();
+        async move {
+            let draft_id = draft_id?;
+            if let Some(email) = state.store.email_with_body(&draft_id) {
+                return Some(email);
+            }
+            let emails = client?.get_email_bodies(&[draft_id]).await.ok()?;
+            state.store.merge_emails(&emails, "");
+            emails.into_iter().next()
+        }
+    });
 
     view! {
         <div class="compose-view">
-            <h2>"New Message"</h2>
-            <ComposeForm
-                initial_to=String::new()
-                initial_cc=String::new()
-                initial_bcc=String::new()
-                initial_subject=String::new()
-                initial_body=String::new()
-                on_cancel=on_cancel
-                on_sent=on_sent
-            />
+            <h2>
+                {move || if draft_param.get().is_some() { "Edit Draft" } else { "New Message" }}
+            </h2>
+            {move || match draft.get() {
+                None => view! { <div class="loading">"Loading..."</div> }.into_any(),
+                Some(email) => {
+                    let draft_id = email.as_ref().and_then(|e| e.id.clone());
+                    let prefill = email.as_ref().map(draft_prefill).unwrap_or_default();
+                    // Keep sending a draft from the identity it was written as
+                    let (identity_id, from_address) = email
+                        .as_ref()
+                        .and_then(|e| {
+                            let from: Vec<&EmailAddress> = e.from.iter().flatten().collect();
+                            state.identities.with_untracked(|identities| {
+                                matching_identity(identities, &from)
+                                    .map(|(i, address)| (i.id.clone(), address))
+                            })
+                        })
+                        .unzip();
+                    view! {
+                        <ComposeForm
+                            initial_to=prefill.to
+                            initial_cc=prefill.cc
+                            initial_bcc=prefill.bcc
+                            initial_reply_to=prefill.reply_to
+                            initial_subject=prefill.subject
+                            initial_body=prefill.body
+                            initial_attachments=prefill.attachments
+                            identity_id=identity_id
+                            from_address=from_address
+                            in_reply_to=prefill.in_reply_to
+                            references=prefill.references
+                            draft_id=draft_id
+                            on_close=on_close.clone()
+                        />
+                    }.into_any()
+                }
+            }}
         </div>
     }
 }
 
-/// Inline compose for replies within the thread view.
+/// Inline compose for replies and forwards within the thread view.
 #[component]
 pub fn ComposeInline(email_id: String) -> impl IntoView {
     let state = use_context::<AppState>().expect("AppState to be provided");
-    let is_reply_all = state.reply_all.get();
-
-    // Find the email being replied to and pre-fill fields
-    let (init_to, init_cc, init_subject, init_body) = {
-        let client = state.client.get();
-        let identities = state.identities.get();
-        let my_email = identities.first().map(|i| i.email.clone()).unwrap_or_default();
-
-        // We need to look up this email. For now, use a simple approach:
-        // the thread view already loaded the emails, so we'll fetch again.
-        // In a real app, we'd cache this.
-        let email_id_clone = email_id.clone();
-        let (to, cc, subject, body) = if let Some(client) = client {
-            // We'll set defaults; actual pre-fill happens async
-            let _ = (client, email_id_clone);
-            (String::new(), String::new(), String::new(), String::new())
-        } else {
-            (String::new(), String::new(), String::new(), String::new())
-        };
 
-        // Prefill will be done in a resource below
-        let _ = (my_email, is_reply_all);
-        (to, cc, subject, body)
+    // Load the email being replied to (usually already in the store from
+    // the thread view); the form is only rendered once it has loaded so the
+    // pre-filled fields and threading headers are in place. It's loaded once,
+    // so connecting later doesn't rebuild the form and lose edits.
+    let original = LocalResource::new(move || {
+        let client = state.client.get_untracked();
+        let email_id = email_id.clone();
+        async move {
+            if let Some(email) = state.store.email_with_body(&email_id) {
+                return Some(email);
+            }
+            let emails = client?.get_email_bodies(&[email_id]).await.ok()?;
+            state.store.merge_emails(&emails, "");
+            emails.into_iter().next()
+        }
+    });
+
+    view! {
+        <div class="compose-inline">
+            {move || match original.get() {
+                None => view! { <div class="loading">"Loading..."</div> }.into_any(),
+                Some(email) => {
+                    // Switching between reply and forward rebuilds the form
+                    let mode = state.reply_mode.get();
+                    // Answer from whichever of our addresses it was sent to
+                    let identities = state.identities.get_untracked();
+                    let recipients: Vec<&EmailAddress> = email
+                        .iter()
+                        .flat_map(|e| e.to.iter().chain(&e.cc).chain(&e.bcc).flatten())
+                        .collect();
+                    let matched = matching_identity(&identities, &recipients);
+                    let identity = matched.as_ref().map(|(i, _)| *i).or_else(|| identities.first());
+                    let from_address = matched.map(|(_, address)| address);
+                    let identity_id = identity.map(|i| i.id.clone());
+                    let my_email = from_address
+                        .clone()
+                        .or_else(|| identity.map(|i| i.email.clone()))
+                        .unwrap_or_default();
+                    let forwarded: Vec<String> = match mode {
+                        ReplyMode::Forward | ReplyMode::ForwardAsAttachment => {
+                            email.as_ref().and_then(|e| e.id.clone()).into_iter().collect()
+                        }
+                        ReplyMode::Reply | ReplyMode::ReplyAll => vec![],
+                    };
+                    let prefill = email
+                        .map(|email| match mode {
+                            ReplyMode::Reply => reply_prefill(&email, &my_email, false),
+                            ReplyMode::ReplyAll => reply_prefill(&email, &my_email, true),
+                            ReplyMode::Forward => forward_prefill(&email),
+                            ReplyMode::ForwardAsAttachment => forward_attachment_prefill(&email),
+                        })
+                        .unwrap_or_default();
+
+                    let on_close = move || {
+                        state.reply_to_email.set(None);
+                    };
+
+                    view! {
+                        <ComposeForm
+                            initial_to=prefill.to
+                            initial_cc=prefill.cc
+                            initial_bcc=String::new()
+                            initial_subject=prefill.subject
+                            initial_body=prefill.body
+                            initial_attachments=prefill.attachments
+                            identity_id=identity_id
+                            from_address=from_address
+                            in_reply_to=prefill.in_reply_to
+                            references=prefill.references
+                            forwarded=forwarded
+                            on_close=on_close
+                        />
+                    }.into_any()
+                }
+            }}
+        </div>
+    }
+}
+
+/// Initial field values for a reply or a reopened draft.
+#[derive(Default)]
+struct Prefill {
+    to: String,
+    cc: String,
+    bcc: String,
+    reply_to: String,
+    subject: String,
+    body: String,
+    attachments: Vec<EmailBodyPart>,
+    in_reply_to: Vec<String>,
+    references: Vec<String>,
+}
+
+/// Put a saved draft back into the form as it was.
+fn draft_prefill(email: &Email) -> Prefill {
+    let join = |addrs: &Option<Vec<EmailAddress>>| {
+        addrs
+            .iter()
+            .flatten()
+            .map(|a| a.email.clone())
+            .collect::<Vec<_>>()
+            .join(", ")
     };
+    Prefill {
+        to: join(&email.to),
+        cc: join(&email.cc),
+        bcc: join(&email.bcc),
+        reply_to: join(&email.reply_to),
+        subject: email.subject.clone().unwrap_or_default(),
+        body: text_body_value(email),
+        attachments: email.attachments.clone().unwrap_or_default(),
+        in_reply_to: email.in_reply_to.clone().unwrap_or_default(),
+        references: email.references.clone().unwrap_or_default(),
+    }
+}
 
-    let to_signal = RwSignal::new(init_to);
-    let cc_signal = RwSignal::new(init_cc);
-    let subject_signal = RwSignal::new(init_subject);
-    let body_signal = RwSignal::new(init_body);
-
-    // Async prefill from email data
-    let email_id_fetch = email_id.clone();
-    spawn_local(async move {
-        let client = state.client.get();
-        let Some(client) = client else { return };
-        let identities = state.identities.get();
-        let my_email = identities.first().map(|i| i.email.clone()).unwrap_or_default();
-
-        let emails = client
-            .get_email_bodies(&[email_id_fetch])
-            .await
-            .ok()
-            .unwrap_or_default();
-        let Some(email) = emails.first() else { return };
+/// The decoded value of the first text/plain body part, if fetched.
+fn text_body_value(email: &Email) -> String {
+    email
+        .text_body
+        .as_ref()
+        .and_then(|parts| parts.first())
+        .and_then(|part| part.part_id.as_ref())
+        .and_then(|part_id| {
+            email
+                .body_values
+                .as_ref()
+                .and_then(|bv| bv.get(part_id))
+                .map(|v| v.value.clone())
+        })
+        .unwrap_or_default()
+}
 
-        // Build subject
-        let orig_subject = email.subject.as_deref().unwrap_or("");
-        let re_subject = if orig_subject.starts_with("Re: ") {
-            orig_subject.to_string()
-        } else {
-            format!("Re: {orig_subject}")
-        };
-        subject_signal.set(re_subject);
+fn reply_prefill(email: &Email, my_email: &str, is_reply_all: bool) -> Prefill {
+    // Build subject
+    let orig_subject = email.subject.as_deref().unwrap_or("");
+    let subject = if orig_subject.starts_with("Re: ") {
+        orig_subject.to_string()
+    } else {
+        format!("Re: {orig_subject}")
+    };
+
+    // Build To and Cc
+    let (to, cc) = if is_reply_all {
+        // To = original From + original To (minus self)
+        let mut to_list: Vec<String> = Vec::new();
+        if let Some(from) = &email.from {
+            to_list.extend(from.iter().map(|a| a.email.clone()));
+        }
+        if let Some(to) = &email.to {
+            to_list.extend(
+                to.iter()
+                    .filter(|a| a.email != my_email)
+                    .map(|a| a.email.clone()),
+            );
+        }
 
-        // Build To
+        // Cc = original Cc
+        let cc_list = email
+            .cc
+            .as_ref()
+            .map(|addrs| {
+                addrs
+                    .iter()
+                    .filter(|a| a.email != my_email)
+                    .map(|a| a.email.clone())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            })
+            .unwrap_or_default();
+        (to_list.join(", "), cc_list)
+    } else {
         let from_addrs = email
             .from
             .as_ref()
@@ -119,180 +278,653 @@ pub fn ComposeInline(email_id: String) -> impl IntoView {
                     .join(", ")
             })
             .unwrap_or_default();
+        (from_addrs, String::new())
+    };
 
-        if is_reply_all {
-            // To = original From + original To (minus self)
-            let mut to_list: Vec<String> = Vec::new();
-            if let Some(from) = &email.from {
-                to_list.extend(from.iter().map(|a| a.email.clone()));
-            }
-            if let Some(to) = &email.to {
-                to_list.extend(
-                    to.iter()
-                        .filter(|a| a.email != my_email)
-                        .map(|a| a.email.clone()),
-                );
-            }
-            to_signal.set(to_list.join(", "));
+    // Build body with quoted text
+    let orig_from = email
+        .from
+        .as_ref()
+        .and_then(|a| a.first())
+        .map(|a| a.to_string())
+        .unwrap_or_default();
+    let orig_date = email.received_at.as_deref().unwrap_or("");
+    let orig_body = text_body_value(email);
 
-            // Cc = original Cc
-            let cc_list = email
-                .cc
-                .as_ref()
-                .map(|addrs| {
-                    addrs
-                        .iter()
-                        .filter(|a| a.email != my_email)
-                        .map(|a| a.email.clone())
-                        .collect::<Vec<_>>()
-                        .join(", ")
-                })
-                .unwrap_or_default();
-            cc_signal.set(cc_list);
+    let quoted = orig_body
+        .lines()
+        .map(|line| format!("> {line}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let body = format!("\n\nOn {orig_date}, {orig_from} wrote:\n{quoted}");
+
+    // Threading headers (RFC 5322 §3.6.4): In-Reply-To is the parent's
+    // Message-ID; References is the parent's References (or In-Reply-To if
+    // it has none) followed by the parent's Message-ID.
+    let in_reply_to = email.message_id.clone().unwrap_or_default();
+    let mut references = email
+        .references
+        .clone()
+        .or_else(|| email.in_reply_to.clone())
+        .unwrap_or_default();
+    references.extend(in_reply_to.iter().cloned());
+
+    Prefill {
+        to,
+        cc,
+        subject,
+        body,
+        in_reply_to,
+        references,
+        ..Default::default()
+    }
+}
+
+/// How often unsaved edits are written to the Drafts mailbox.
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// "Fwd: " plus the original subject, unless it's already there.
+fn forward_subject(email: &Email) -> String {
+    let orig_subject = email.subject.as_deref().unwrap_or("");
+    if orig_subject.starts_with("Fwd: ") {
+        orig_subject.to_string()
+    } else {
+        format!("Fwd: {orig_subject}")
+    }
+}
+
+/// Forward inline: the original headers and text quoted in the body, with
+/// its attachments re-sent by blobId so nothing is uploaded again.
+fn forward_prefill(email: &Email) -> Prefill {
+    let addresses = |addrs: &Option<Vec<EmailAddress>>| {
+        addrs
+            .iter()
+            .flatten()
+            .map(|a| a.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let mut header = format!(
+        "---------- Forwarded message ----------\nFrom: {}\nDate: {}\nSubject: {}\nTo: {}\n",
+        addresses(&email.from),
+        email.sent_at.as_deref().or(email.received_at.as_deref()).unwrap_or(""),
+        email.subject.as_deref().unwrap_or(""),
+        addresses(&email.to),
+    );
+    if email.cc.as_ref().is_some_and(|cc| !cc.is_empty()) {
+        header.push_str(&format!("Cc: {}\n", addresses(&email.cc)));
+    }
+
+    Prefill {
+        subject: forward_subject(email),
+        body: format!("\n\n{header}\n{}", text_body_value(email)),
+        attachments: email.attachments.clone().unwrap_or_default(),
+        ..Default::default()
+    }
+}
+
+/// Forward as attachment: the original message, unchanged, attached as
+/// message/rfc822 using its own blob.
+fn forward_attachment_prefill(email: &Email) -> Prefill {
+    let name = eml_filename(email.subject.as_deref().unwrap_or(""));
+    let attachments = email
+        .blob_id
+        .clone()
+        .map(|blob_id| EmailBodyPart {
+            part_id: None,
+            blob_id: Some(blob_id),
+            size: email.size,
+            type_: Some("message/rfc822".to_string()),
+            name: Some(name),
+            charset: None,
+            disposition: Some("attachment".to_string()),
+            cid: None,
+        })
+        .into_iter()
+        .collect();
+
+    Prefill {
+        subject: forward_subject(email),
+        attachments,
+        ..Default::default()
+    }
+}
+
+/// A filename for a forwarded message, from its subject with characters
+/// that aren't allowed in filenames replaced.
+fn eml_filename(subject: &str) -> String {
+    let name: String = subject
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => ' ',
+            c => c,
+        })
+        .take(100)
+        .collect();
+    // Leading dots would hide the file; trailing ones and spaces are dropped
+    let name = name.trim().trim_matches('.').trim();
+    if name.is_empty() {
+        "message.eml".to_string()
+    } else {
+        format!("{name}.eml")
+    }
+}
+
+/// The identity's plain-text signature, if it has one. The form is plain
+/// text, so the HTML signature isn't used.
+fn signature(identity: &Identity) -> Option<&str> {
+    identity.text_signature.as_deref().filter(|s| !s.trim().is_empty())
+}
+
+/// A signature with its "-- " separator line (RFC 3676 §4.3).
+fn signature_block(signature: &str) -> String {
+    format!("\n\n-- \n{}", signature.trim_end())
+}
+
+/// Replace the `old` signature in `body` with `new`. The signature goes
+/// above the `quoted` text of a reply or forward, or at the end otherwise.
+/// If the old signature has been edited it's left as it is and the new one
+/// isn't added, so the body never ends up with two.
+fn swap_signature(body: &str, old: Option<&str>, new: Option<&str>, quoted: &str) -> String {
+    let mut body = body.to_string();
+    if let Some(old) = old {
+        let old_block = signature_block(old);
+        let Some(pos) = body.find(&old_block) else {
+            return body;
+        };
+        body.replace_range(pos..pos + old_block.len(), "");
+    }
+    if let Some(new) = new {
+        let pos = if quoted.is_empty() {
+            body.len()
         } else {
-            to_signal.set(from_addrs);
+            body.rfind(quoted).unwrap_or(body.len())
+        };
+        body.insert_str(pos, &signature_block(new));
+    }
+    body
+}
+
+/// Remove the `old` addresses from a comma-separated field and add the
+/// `new` ones that aren't already there.
+fn swap_addresses(field: &str, old: &[EmailAddress], new: &[EmailAddress]) -> String {
+    let mut addresses: Vec<String> = parse_addresses(field)
+        .into_iter()
+        .filter(|a| !old.iter().any(|o| o.email.eq_ignore_ascii_case(&a.email)))
+        .map(|a| a.email)
+        .collect();
+    for address in new {
+        if !addresses.iter().any(|a| a.eq_ignore_ascii_case(&address.email)) {
+            addresses.push(address.email.clone());
         }
+    }
+    addresses.join(", ")
+}
 
-        // Build body with quoted text
-        let orig_from = email
-            .from
-            .as_ref()
-            .and_then(|a| a.first())
-            .map(|a| a.to_string())
-            .unwrap_or_default();
-        let orig_date = email.received_at.as_deref().unwrap_or("");
-        let orig_body = email
-            .text_body
-            .as_ref()
-            .and_then(|parts| parts.first())
-            .and_then(|part| part.part_id.as_ref())
-            .and_then(|part_id| {
-                email
-                    .body_values
-                    .as_ref()
-                    .and_then(|bv| bv.get(part_id))
-                    .map(|v| v.value.clone())
-            })
-            .unwrap_or_default();
+/// The identity for the first of `addresses` that is one of ours, and the
+/// address to send as. Exact identities win over wildcard ones.
+pub fn matching_identity<'a>(
+    identities: &'a [Identity],
+    addresses: &[&EmailAddress],
+) -> Option<(&'a Identity, String)> {
+    addresses.iter().find_map(|address| {
+        identities
+            .iter()
+            .find(|i| i.email.eq_ignore_ascii_case(&address.email))
+            .or_else(|| identities.iter().find(|i| may_send_as(i, &address.email)))
+            .map(|i| (i, address.email.clone()))
+    })
+}
 
-        let quoted = orig_body
-            .lines()
-            .map(|line| format!("> {line}"))
-            .collect::<Vec<_>>()
-            .join("\n");
+/// The domain of a wildcard identity (`*@example.com`, RFC 8621 §6), which
+/// may send from any address there.
+pub fn wildcard_domain(identity: &Identity) -> Option<&str> {
+    identity.email.strip_prefix("*@")
+}
 
-        body_signal.set(format!(
-            "\n\nOn {orig_date}, {orig_from} wrote:\n{quoted}"
-        ));
-    });
+/// Whether `identity` can send from `address`.
+fn may_send_as(identity: &Identity, address: &str) -> bool {
+    match wildcard_domain(identity) {
+        Some(domain) => {
+            address.split_once('@').is_some_and(|(local, _)| !local.is_empty())
+                && sender_domain(address).is_some_and(|d| d.eq_ignore_ascii_case(domain))
+        }
+        None => identity.email.eq_ignore_ascii_case(address),
+    }
+}
 
-    let on_cancel = move |_| {
-        state.reply_to_email.set(None);
-    };
+/// Undo windows offered in the compose form, in seconds.
+const UNDO_SEND_CHOICES: &[u64] = &[0, 5, 10, 20, 30];
 
-    let on_sent = move || {
-        state.reply_to_email.set(None);
-    };
+/// Turn a `datetime-local` value into a release time, checking it's in the
+/// future and no further ahead than the server's `maxDelayedSend`.
+fn scheduled_release(local: &str, max_delayed_send: u64) -> Result<Release, String> {
+    // Without an offset the value is parsed as local time
+    let at = js_sys::Date::new(&JsValue::from_str(local));
+    let delay_ms = at.get_time() - js_sys::Date::now();
+    if delay_ms.is_nan() {
+        return Err("Invalid send time".to_string());
+    }
+    if delay_ms <= 0.0 {
+        return Err("The send time is in the past".to_string());
+    }
+    if delay_ms / 1000.0 > max_delayed_send as f64 {
+        return Err(format!(
+            "Messages can be scheduled at most {} ahead",
+            format_delay(max_delayed_send)
+        ));
+    }
+    // HOLDUNTIL takes whole seconds in UTC
+    let iso = String::from(at.to_iso_string());
+    let seconds = iso.split('.').next().unwrap_or(&iso);
+    Ok(Release::At(format!("{}Z", seconds.trim_end_matches('Z'))))
+}
 
-    view! {
-        <div class="compose-inline">
-            <ComposeForm
-                initial_to=to_signal.get()
-                initial_cc=cc_signal.get()
-                initial_bcc=String::new()
-                initial_subject=subject_signal.get()
-                initial_body=body_signal.get()
-                on_cancel=on_cancel
-                on_sent=on_sent
-            />
-        </div>
+fn format_delay(seconds: u64) -> String {
+    match seconds {
+        s if s >= 86_400 => format!("{} days", s / 86_400),
+        s if s >= 3_600 => format!("{} hours", s / 3_600),
+        s => format!("{} minutes", s / 60),
     }
 }
 
+/// An attachment in the compose form, either uploading or ready to send.
+#[derive(Clone)]
+struct ComposeAttachment {
+    key: u64,
+    name: String,
+    type_: String,
+    size: u64,
+    progress: f64,
+    blob_id: Option<String>,
+    error: Option<String>,
+}
+
+fn update_attachment(
+    attachments: RwSignal<Vec<ComposeAttachment>>,
+    key: u64,
+    f: impl FnOnce(&mut ComposeAttachment),
+) {
+    attachments.update(|list| {
+        if let Some(a) = list.iter_mut().find(|a| a.key == key) {
+            f(a);
+        }
+    });
+}
+
 /// Shared compose form used by both ComposeView and ComposeInline.
 #[component]
 fn ComposeForm(
     initial_to: String,
     initial_cc: String,
     initial_bcc: String,
+    #[prop(optional)] initial_reply_to: String,
     initial_subject: String,
     initial_body: String,
-    on_cancel: impl Fn(leptos::ev::MouseEvent) + 'static,
-    on_sent: impl Fn() + Clone + 'static,
+    #[prop(optional)] initial_attachments: Vec<EmailBodyPart>,
+    /// Identity to send as; the first one if unset or unknown.
+    #[prop(optional_no_strip)] identity_id: Option<String>,
+    /// The address to send from with a wildcard identity.
+    #[prop(optional_no_strip)] from_address: Option<String>,
+    #[prop(optional)] in_reply_to: Vec<String>,
+    #[prop(optional)] references: Vec<String>,
+    /// Emails being forwarded, marked `$forwarded` once sent.
+    #[prop(optional)] forwarded: Vec<String>,
+    /// The saved draft being edited, replaced on each autosave.
+    #[prop(optional_no_strip)] draft_id: Option<String>,
+    on_close: impl Fn() + Clone + 'static,
 ) -> impl IntoView {
     let state = use_context::<AppState>().expect("AppState to be provided");
 
+    let identities = state.identities.get_untracked();
+    let identity = identities
+        .iter()
+        .find(|i| Some(&i.id) == identity_id.as_ref())
+        .or_else(|| identities.first())
+        .cloned();
+
+    // The quoted or forwarded text the signature goes above
+    let quoted = StoredValue::new(initial_body.clone());
+
+    // A new message starts with the identity's signature and default
+    // Reply-To and Bcc; a draft already has whatever was chosen.
+    let (initial_body, initial_bcc, initial_reply_to) = match (&identity, &draft_id) {
+        (Some(identity), None) => (
+            swap_signature(&initial_body, None, signature(identity), &initial_body),
+            swap_addresses(&initial_bcc, &[], identity.bcc.as_deref().unwrap_or_default()),
+            swap_addresses(
+                &initial_reply_to,
+                &[],
+                identity.reply_to.as_deref().unwrap_or_default(),
+            ),
+        ),
+        _ => (initial_body, initial_bcc, initial_reply_to),
+    };
+
+    let identity_id = RwSignal::new(identity.map(|i| i.id));
+    let from_address = RwSignal::new(from_address.unwrap_or_default());
+    // Set when the selected identity is a wildcard, so the form asks which
+    // address to send from
+    let from_domain = move || {
+        let selected = identity_id.get();
+        state.identities.with(|identities| {
+            let identity = identities.iter().find(|i| Some(&i.id) == selected.as_ref())?;
+            wildcard_domain(identity).map(|d| d.to_string())
+        })
+    };
     let to = RwSignal::new(initial_to);
     let cc = RwSignal::new(initial_cc);
     let bcc = RwSignal::new(initial_bcc);
+    let reply_to = RwSignal::new(initial_reply_to);
     let subject = RwSignal::new(initial_subject);
     let body = RwSignal::new(initial_body);
+    let in_reply_to = StoredValue::new(in_reply_to);
+    let references = StoredValue::new(references);
+    let forwarded = StoredValue::new(forwarded);
+    let attachments: RwSignal<Vec<ComposeAttachment>> = RwSignal::new(
+        initial_attachments
+            .into_iter()
+            .enumerate()
+            .map(|(key, part)| ComposeAttachment {
+                key: key as u64,
+                name: part.name.unwrap_or_else(|| "attachment".to_string()),
+                type_: part.type_.unwrap_or_default(),
+                size: part.size.unwrap_or_default(),
+                progress: 1.0,
+                blob_id: part.blob_id,
+                error: None,
+            })
+            .collect(),
+    );
+    let next_attachment_key = StoredValue::new(attachments.with_untracked(|a| a.len() as u64));
+    let drag_over = RwSignal::new(false);
     let sending = RwSignal::new(false);
     let error_msg = RwSignal::new(Option::<String>::None);
+    let draft_id = StoredValue::new(draft_id);
+    let dirty = StoredValue::new(false);
+    let saving_draft = RwSignal::new(false);
+    let discarding = RwSignal::new(false);
+    // Saving or discarding the draft; the other actions wait for it
+    let draft_busy = move || saving_draft.get() || discarding.get();
+    let draft_saved = RwSignal::new(false);
+    let send_at = RwSignal::new(String::new());
 
-    let on_sent_clone = on_sent.clone();
-    let on_submit = move |ev: leptos::ev::SubmitEvent| {
+    // Holding submissions (for undo and scheduling) needs FUTURERELEASE
+    let max_delayed_send = state
+        .client
+        .get_untracked()
+        .and_then(|c| c.submission_capabilities())
+        .filter(|s| s.supports_future_release())
+        .map(|s| s.max_delayed_send);
+
+    let max_attachments_size = state
+        .client
+        .get_untracked()
+        .and_then(|c| c.mail_capabilities())
+        .map(|m| m.max_size_attachments_per_email)
+        .filter(|&max| max > 0);
+    let attachments_size = move || {
+        attachments.with(|list| {
+            list.iter()
+                .filter(|a| a.error.is_none())
+                .map(|a| a.size)
+                .sum::<u64>()
+        })
+    };
+    let over_limit = move || max_attachments_size.is_some_and(|max| attachments_size() > max);
+    let uploading = move || {
+        attachments.with(|list| list.iter().any(|a| a.blob_id.is_none() && a.error.is_none()))
+    };
+    // Failed uploads must be removed before sending, so nothing the user
+    // saw in the form goes missing
+    let upload_failed = move || attachments.with(|list| list.iter().any(|a| a.error.is_some()));
+
+    // Start uploading each file immediately; the blobId is filled in on completion.
+    let add_files = move |files: web_sys::FileList| {
+        for i in 0..files.length() {
+            let Some(file) = files.get(i) else { continue };
+            let key = next_attachment_key.get_value();
+            next_attachment_key.set_value(key + 1);
+            attachments.update(|list| {
+                list.push(ComposeAttachment {
+                    key,
+                    name: file.name(),
+                    type_: file.type_(),
+                    size: file.size() as u64,
+                    progress: 0.0,
+                    blob_id: None,
+                    error: None,
+                })
+            });
+
+            spawn_local(async move {
+                let Some(client) = state.client.get_untracked() else {
+                    update_attachment(attachments, key, |a| a.error = Some("Not connected".to_string()));
+                    return;
+                };
+                let on_progress = move |p| update_attachment(attachments, key, |a| a.progress = p);
+                match upload_file(&client, &file, on_progress).await {
+                    Ok(upload) => update_attachment(attachments, key, |a| {
+                        a.progress = 1.0;
+                        a.blob_id = Some(upload.blob_id);
+                        a.type_ = upload.type_;
+                        a.size = upload.size;
+                    }),
+                    Err(e) => update_attachment(attachments, key, |a| a.error = Some(e)),
+                }
+            });
+        }
+    };
+
+    let on_file_change = move |ev: leptos::ev::Event| {
+        let input: web_sys::HtmlInputElement = event_target(&ev);
+        if let Some(files) = input.files() {
+            add_files(files);
+        }
+        // Allow picking the same file again
+        input.set_value("");
+    };
+
+    let on_drag_over = move |ev: leptos::ev::DragEvent| {
+        ev.prevent_default();
+        drag_over.set(true);
+    };
+
+    let on_drag_leave = move |_: leptos::ev::DragEvent| {
+        drag_over.set(false);
+    };
+
+    let on_drop = move |ev: leptos::ev::DragEvent| {
         ev.prevent_default();
+        drag_over.set(false);
+        if let Some(files) = ev.data_transfer().and_then(|dt| dt.files()) {
+            add_files(files);
+        }
+    };
+
+    // The message as currently entered, from the selected identity (at the
+    // entered From address if it's a wildcard). Attachments still uploading
+    // are left out.
+    let build_email = move || {
+        let identities = state.identities.get_untracked();
+        let selected = identity_id.get_untracked();
+        let identity = identities.iter().find(|i| Some(&i.id) == selected.as_ref())?;
+        let attachment_parts: Vec<EmailBodyPart> = attachments.with_untracked(|list| {
+            list.iter()
+                .filter_map(|a| {
+                    Some(EmailBodyPart {
+                        part_id: None,
+                        blob_id: Some(a.blob_id.clone()?),
+                        size: Some(a.size),
+                        type_: Some(a.type_.clone()),
+                        name: Some(a.name.clone()),
+                        charset: None,
+                        disposition: Some("attachment".to_string()),
+                        cid: None,
+                    })
+                })
+                .collect()
+        });
+        let from = match wildcard_domain(identity) {
+            Some(_) => from_address.get_untracked().trim().to_string(),
+            None => identity.email.clone(),
+        };
+        let email = OutgoingEmail {
+            from: vec![EmailAddress {
+                name: identity.name.clone(),
+                email: from,
+            }],
+            to: parse_addresses(&to.get_untracked()),
+            cc: parse_addresses(&cc.get_untracked()),
+            bcc: parse_addresses(&bcc.get_untracked()),
+            reply_to: parse_addresses(&reply_to.get_untracked()),
+            subject: subject.get_untracked(),
+            text_body: body.get_untracked(),
+            in_reply_to: in_reply_to.get_value(),
+            references: references.get_value(),
+            attachments: attachment_parts,
+            forwarded: forwarded.get_value(),
+            ..Default::default()
+        };
+        Some((identity.id.clone(), email))
+    };
+
+    // Switching identity swaps its signature and default Reply-To and Bcc
+    // for the new one's, leaving anything the user typed alone.
+    let on_identity_change = move |ev: leptos::ev::Event| {
+        let new_id = event_target_value(&ev);
+        let (old, new) = state.identities.with_untracked(|identities| {
+            let find = |id: Option<&String>| identities.iter().find(|i| Some(&i.id) == id).cloned();
+            (find(identity_id.get_untracked().as_ref()), find(Some(&new_id)))
+        });
+        let Some(new) = new else { return };
+        let old_signature = old.as_ref().and_then(signature);
+        let quoted = quoted.get_value();
+        body.update(|b| *b = swap_signature(b, old_signature, signature(&new), &quoted));
+        let old_bcc = old.as_ref().and_then(|i| i.bcc.clone()).unwrap_or_default();
+        bcc.update(|f| *f = swap_addresses(f, &old_bcc, new.bcc.as_deref().unwrap_or_default()));
+        let old_reply_to = old.as_ref().and_then(|i| i.reply_to.clone()).unwrap_or_default();
+        let new_reply_to = new.reply_to.as_deref().unwrap_or_default();
+        reply_to.update(|f| *f = swap_addresses(f, &old_reply_to, new_reply_to));
+        identity_id.set(Some(new.id));
+    };
+
+    // Any edit after the form opens leaves the draft out of date
+    Effect::new(move |prev: Option<()>| {
+        identity_id.track();
+        to.track();
+        cc.track();
+        bcc.track();
+        reply_to.track();
+        subject.track();
+        body.track();
+        attachments.track();
+        if prev.is_some() {
+            dirty.set_value(true);
+            draft_saved.set(false);
+        }
+    });
+
+    // Save the draft, replacing the previous version, unless nothing has
+    // changed or a save, send or discard is already underway.
+    let save_draft = move || {
+        if !dirty.get_value()
+            || saving_draft.get_untracked()
+            || discarding.get_untracked()
+            || sending.get_untracked()
+        {
+            return;
+        }
+        let Some(client) = state.client.get_untracked() else { return };
+        let Some((_, email)) = build_email() else { return };
+        let mailboxes = state.mailboxes.get_untracked();
+        let Some(drafts) = client.find_mailbox_by_role(&mailboxes, "drafts") else { return };
+        let drafts_id = drafts.id.clone();
+
+        dirty.set_value(false);
+        saving_draft.set(true);
+        spawn_local(async move {
+            let previous = draft_id.get_value();
+            match client.save_draft(&email, &drafts_id, previous.as_deref()).await {
+                Ok(id) => {
+                    draft_id.set_value(Some(id));
+                    draft_saved.set(true);
+                }
+                Err(_) => dirty.set_value(true),
+            }
+            saving_draft.set(false);
+        });
+    };
 
-        let to_val = to.get();
-        let cc_val = cc.get();
-        let bcc_val = bcc.get();
-        let subject_val = subject.get();
-        let body_val = body.get();
-        let on_sent = on_sent_clone.clone();
+    if let Ok(handle) = set_interval_with_handle(save_draft, AUTOSAVE_INTERVAL) {
+        on_cleanup(move || handle.clear());
+    }
+
+    let on_close_submit = on_close.clone();
+    let on_submit = move |ev: leptos::ev::SubmitEvent| {
+        ev.prevent_default();
+        if uploading()
+            || upload_failed()
+            || over_limit()
+            || saving_draft.get_untracked()
+            || discarding.get_untracked()
+        {
+            return;
+        }
+        let Some((identity_id, mut email)) = build_email() else {
+            error_msg.set(Some("No identity found".to_string()));
+            return;
+        };
+        // A wildcard identity needs a real address at its domain
+        let invalid_from = state.identities.with_untracked(|identities| {
+            let identity = identities.iter().find(|i| i.id == identity_id)?;
+            let domain = wildcard_domain(identity)?;
+            let from = email.from.first()?;
+            (!may_send_as(identity, &from.email)).then(|| domain.to_string())
+        });
+        if let Some(domain) = invalid_from {
+            error_msg.set(Some(format!("Enter a From address at {domain}")));
+            return;
+        }
+        email.replaces_draft = draft_id.get_value();
+        email.release = match max_delayed_send {
+            Some(max) if !send_at.get_untracked().is_empty() => {
+                match scheduled_release(&send_at.get_untracked(), max) {
+                    Ok(release) => Some(release),
+                    Err(msg) => {
+                        error_msg.set(Some(msg));
+                        return;
+                    }
+                }
+            }
+            Some(max) => {
+                let undo_seconds = state.undo_send_seconds.get_untracked().min(max);
+                (undo_seconds > 0).then_some(Release::After(undo_seconds))
+            }
+            None => None,
+        };
+        let release = email.release.clone();
+        let on_close = on_close_submit.clone();
 
         sending.set(true);
         error_msg.set(None);
 
         spawn_local(async move {
-            let client = state.client.get();
-            let Some(client) = client else {
-                error_msg.set(Some("Not connected".to_string()));
-                sending.set(false);
-                return;
-            };
-
-            let identities = state.identities.get();
-            let Some(identity) = identities.first() else {
-                error_msg.set(Some("No identity found".to_string()));
-                sending.set(false);
-                return;
-            };
-
-            let mailboxes = state.mailboxes.get();
-            let drafts_id = client
-                .find_mailbox_by_role(&mailboxes, "drafts")
-                .map(|m| m.id.clone())
-                .unwrap_or_default();
-            let sent_id = client
-                .find_mailbox_by_role(&mailboxes, "sent")
-                .map(|m| m.id.clone())
-                .unwrap_or_default();
-
-            let from_addrs = vec![EmailAddress {
-                name: identity.name.clone(),
-                email: identity.email.clone(),
-            }];
-            let to_addrs = parse_addresses(&to_val);
-            let cc_addrs = parse_addresses(&cc_val);
-            let bcc_addrs = parse_addresses(&bcc_val);
-
-            match client
-                .send_email(
-                    &identity.id,
-                    &from_addrs,
-                    &to_addrs,
-                    &cc_addrs,
-                    &bcc_addrs,
-                    &subject_val,
-                    &body_val,
-                    &drafts_id,
-                    &sent_id,
-                )
-                .await
-            {
-                Ok(()) => {
-                    on_sent();
+            // Queued messages go out from the outbox once we're back online
+            match send_or_queue(state, identity_id, email).await {
+                Ok(SendOutcome::Sent(sent)) => {
+                    if let Some(Release::After(seconds)) = release {
+                        state.pending_undo.set(Some(UndoableSend {
+                            email_id: sent.email_id,
+                            submission_id: sent.submission_id,
+                            until: js_sys::Date::now() + seconds as f64 * 1000.0,
+                        }));
+                    }
+                    on_close();
+                }
+                Ok(SendOutcome::Queued) => {
+                    on_close();
                 }
                 Err(e) => {
                     error_msg.set(Some(format!("Send failed: {e}")));
@@ -302,8 +934,109 @@ fn ComposeForm(
         });
     };
 
+    // Closing keeps the draft, saving any edits made since the last autosave.
+    // If they can't be saved (e.g. offline), ask before dropping them.
+    let on_close_keep = on_close.clone();
+    let on_keep = move |_| {
+        if sending.get_untracked() || saving_draft.get_untracked() || discarding.get_untracked()
+        {
+            return;
+        }
+        let on_close = on_close_keep.clone();
+        if !dirty.get_value() {
+            on_close();
+            return;
+        }
+        let client = state.client.get_untracked();
+        let mailboxes = state.mailboxes.get_untracked();
+        let drafts_id = client
+            .as_ref()
+            .and_then(|c| c.find_mailbox_by_role(&mailboxes, "drafts"))
+            .map(|m| m.id.clone());
+        let (Some((_, email)), Some(client), Some(drafts_id)) = (build_email(), client, drafts_id)
+        else {
+            let confirmed = web_sys::window()
+                .and_then(|w| {
+                    w.confirm_with_message("Your latest changes can't be saved. Close anyway?")
+                        .ok()
+                })
+                .unwrap_or(false);
+            if confirmed {
+                on_close();
+            }
+            return;
+        };
+        saving_draft.set(true);
+        spawn_local(async move {
+            let previous = draft_id.get_value();
+            match client.save_draft(&email, &drafts_id, previous.as_deref()).await {
+                Ok(_) => on_close(),
+                Err(e) => {
+                    error_msg.set(Some(format!("Saving draft failed: {e}")));
+                    saving_draft.set(false);
+                }
+            }
+        });
+    };
+
+    // Stops autosave first, so it can't recreate the draft being destroyed
+    let on_discard = move |_| {
+        if sending.get_untracked() || saving_draft.get_untracked() || discarding.get_untracked()
+        {
+            return;
+        }
+        let on_close = on_close.clone();
+        let was_dirty = dirty.get_value();
+        dirty.set_value(false);
+        let (Some(id), Some(client)) = (draft_id.get_value(), state.client.get_untracked()) else {
+            on_close();
+            return;
+        };
+        discarding.set(true);
+        spawn_local(async move {
+            match client.destroy_emails(&[id]).await {
+                Ok(()) => on_close(),
+                Err(e) => {
+                    error_msg.set(Some(format!("Discarding draft failed: {e}")));
+                    dirty.set_value(was_dirty);
+                    discarding.set(false);
+                }
+            }
+        });
+    };
+
     view! {
-        <form class="compose-form" on:submit=on_submit>
+        <form
+            class="compose-form"
+            class:drag-over=move || drag_over.get()
+            on:submit=on_submit
+            on:dragover=on_drag_over
+            on:dragleave=on_drag_leave
+            on:drop=on_drop
+        >
+            <div class="form-field">
+                <label>"From"</label>
+                <select on:change=on_identity_change>
+                    {identities.iter().map(|i| {
+                        let selected = identity_id.get_untracked().as_ref() == Some(&i.id);
+                        let label = match i.name.as_deref().filter(|n| !n.is_empty()) {
+                            Some(name) => format!("{name} <{}>", i.email),
+                            None => i.email.clone(),
+                        };
+                        view! { <option value=i.id.clone() selected=selected>{label}</option> }
+                    }).collect_view()}
+                </select>
+            </div>
+            {move || from_domain().map(|domain| view! {
+                <div class="form-field">
+                    <label>"From address"</label>
+                    <input
+                        type="email"
+                        bind:value=from_address
+                        placeholder=format!("you@{domain}")
+                    />
+                </div>
+            })}
             <div class="form-field">
                 <label>"To"</label>
                 <input type="text" bind:value=to placeholder="recipient@example.com"/>
@@ -316,6 +1049,10 @@ fn ComposeForm(
                 <label>"Bcc"</label>
                 <input type="text" bind:value=bcc/>
             </div>
+            <div class="form-field">
+                <label>"Reply-To"</label>
+                <input type="text" bind:value=reply_to/>
+            </div>
             <div class="form-field">
                 <label>"Subject"</label>
                 <input type="text" bind:value=subject/>
@@ -324,11 +1061,124 @@ fn ComposeForm(
                 <label>"Body"</label>
                 <textarea rows="12" bind:value=body></textarea>
             </div>
+            <div class="compose-attachments">
+                {move || attachments.get().into_iter().map(|a| {
+                    let key = a.key;
+                    let status = if let Some(err) = a.error.clone() {
+                        view! { <span class="attachment-error">{err}</span> }.into_any()
+                    } else if a.blob_id.is_none() {
+                        view! { <progress max="1" value=a.progress></progress> }.into_any()
+                    } else {
+                        ().into_any()
+                    };
+                    let on_remove = move |_| {
+                        attachments.update(|list| list.retain(|a| a.key != key));
+                    };
+                    view! {
+                        <div class="compose-attachment" class:failed=a.error.is_some()>
+                            <span class="attachment-name">{a.name}</span>
+                            <span class="attachment-size">{format_size(a.size)}</span>
+                            {status}
+                            <button type="button" class="attachment-remove" on:click=on_remove>"×"</button>
+                        </div>
+                    }
+                }).collect_view()}
+                <label class="attach-btn">
+                    "Attach files"
+                    <input type="file" multiple style="display: none;" on:change=on_file_change/>
+                </label>
+                <span class="attach-hint">" or drop files here"</span>
+            </div>
+            {move || over_limit().then(|| view! {
+                <div class="error-message">
+                    {format!(
+                        "Attachments total {}, exceeding the server limit of {}",
+                        format_size(attachments_size()),
+                        format_size(max_attachments_size.unwrap_or_default()),
+                    )}
+                </div>
+            })}
+            {move || upload_failed().then(|| view! {
+                <div class="error-message">
+                    "Remove the attachments that failed to upload before sending"
+                </div>
+            })}
+            {max_delayed_send.map(|_| {
+                let on_undo_change = move |ev: leptos::ev::Event| {
+                    let seconds = event_target_value(&ev).parse().unwrap_or(0);
+                    state.undo_send_seconds.set(seconds);
+                    save_undo_send_seconds(seconds);
+                };
+                view! {
+                    <div class="compose-schedule">
+                        <label>
+                            "Send later "
+                            <input type="datetime-local" bind:value=send_at/>
+                        </label>
+                        <label>
+                            "Undo send "
+                            <select on:change=on_undo_change>
+                                {UNDO_SEND_CHOICES.iter().map(|&seconds| {
+                                    let current = state.undo_send_seconds.get_untracked();
+                                    let selected = current == seconds;
+                                    let label = if seconds == 0 {
+                                        "Off".to_string()
+                                    } else {
+                                        format!("{seconds} seconds")
+                                    };
+                                    view! {
+                                        <option value=seconds.to_string() selected=selected>
+                                            {label}
+                                        </option>
+                                    }
+                                }).collect_view()}
+                            </select>
+                        </label>
+                    </div>
+                }
+            })}
             <div class="compose-actions">
-                <button type="submit" disabled=move || sending.get()>
-                    {move || if sending.get() { "Sending..." } else { "Send" }}
+                <button
+                    type="submit"
+                    disabled=move || {
+                        sending.get()
+                            || draft_busy()
+                            || uploading()
+                            || upload_failed()
+                            || over_limit()
+                    }
+                >
+                    {move || match (sending.get(), send_at.get().is_empty()) {
+                        (true, _) => "Sending...",
+                        (false, true) => "Send",
+                        (false, false) => "Schedule",
+                    }}
+                </button>
+                <button
+                    type="button"
+                    disabled=move || sending.get() || draft_busy()
+                    on:click=on_keep
+                >
+                    "Close"
                 </button>
-                <button type="button" on:click=on_cancel>"Cancel"</button>
+                <button
+                    type="button"
+                    disabled=move || sending.get() || draft_busy()
+                    on:click=on_discard
+                >
+                    "Discard"
+                </button>
+                <span class="draft-status">
+                    {move || {
+                        if saving_draft.get() {
+                            "Saving draft..."
+                        } else if draft_saved.get() {
+                            "Draft saved"
+                        } else {
+                            ""
+                        }
+                    }}
+                </span>
             </div>
             {move || error_msg.get().map(|msg| view! {
                 <div class="error-message">{msg}</div>
@@ -337,7 +1187,7 @@ fn ComposeForm(
     }
 }
 
-fn parse_addresses(input: &str) -> Vec<EmailAddress> {
+pub fn parse_addresses(input: &str) -> Vec<EmailAddress> {
     input
         .split(',')
         .map(|s| s.trim())
@@ -348,3 +1198,19 @@ fn parse_addresses(input: &str) -> Vec<EmailAddress> {
         })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eml_filenames() {
+        assert_eq!(eml_filename("Quarterly report"), "Quarterly report.eml");
+        assert_eq!(eml_filename("Re: a/b\\c <d>?"), "Re_ a_b_c _d__.eml");
+        assert_eq!(eml_filename("line\r\nbreak"), "line  break.eml");
+        assert_eq!(eml_filename(""), "message.eml");
+        assert_eq!(eml_filename("  ...  "), "message.eml");
+        assert_eq!(eml_filename(".hidden."), "hidden.eml");
+        assert_eq!(eml_filename(&"x".repeat(300)).len(), 104);
+    }
+}
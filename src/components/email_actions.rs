@@ -0,0 +1,189 @@
+use crate::components::mailbox_sidebar::flatten_tree;
+use crate::router::use_search;
+use crate::state::AppState;
+use jmap_client::{JmapError, Mailbox, MailboxRights};
+use leptos::prelude::*;
+use leptos::task::spawn_local;
+
+#[derive(Clone)]
+enum Action {
+    Move(String),
+    Destroy,
+}
+
+/// Check a right on a mailbox. Servers that omit `myRights` are assumed
+/// to allow everything; the server still enforces the real rights.
+fn may(mailboxes: &[Mailbox], mailbox_id: &str, right: impl Fn(&MailboxRights) -> bool) -> bool {
+    mailboxes
+        .iter()
+        .find(|m| m.id == mailbox_id)
+        .is_some_and(|m| m.my_rights.as_ref().is_none_or(right))
+}
+
+/// Archive / Move / Delete buttons acting on whole threads within the
+/// selected mailbox. Deleting moves to Trash, or destroys permanently when
+/// already in Trash. When searching all folders, threads are moved out of
+/// whichever mailboxes they're in, and Delete always moves to Trash.
+#[component]
+pub fn EmailActions(
+    #[prop(into)] thread_ids: Signal<Vec<String>>,
+    on_done: impl Fn() + Clone + 'static,
+) -> impl IntoView {
+    let state = use_context::<AppState>().expect("AppState to be provided");
+    let busy = RwSignal::new(false);
+    let error_msg = RwSignal::new(Option::<String>::None);
+    let search = use_search();
+    let all_mailboxes = move || search.get().1;
+
+    let role_id = move |role: &str| {
+        state.mailboxes.with(|mailboxes| {
+            mailboxes
+                .iter()
+                .find(|m| m.role.as_deref() == Some(role))
+                .map(|m| m.id.clone())
+        })
+    };
+    let current = move || state.selected_mailbox.get();
+    let in_trash = move || {
+        !all_mailboxes()
+            && current().is_some_and(|id| role_id("trash").as_deref() == Some(id.as_str()))
+    };
+
+    // Moving needs remove rights on the source and add rights on the target.
+    // Across all folders the sources vary, so only the server can check.
+    let can_move_to = move |target: &str| {
+        if all_mailboxes() {
+            return state
+                .mailboxes
+                .with(|mailboxes| may(mailboxes, target, |r| r.may_add_items));
+        }
+        let Some(current) = current() else { return false };
+        current != target
+            && state.mailboxes.with(|mailboxes| {
+                may(mailboxes, &current, |r| r.may_remove_items)
+                    && may(mailboxes, target, |r| r.may_add_items)
+            })
+    };
+    let can_archive = move || role_id("archive").is_some_and(|id| can_move_to(&id));
+    let can_delete = move || {
+        if in_trash() {
+            current().is_some_and(|id| {
+                state
+                    .mailboxes
+                    .with(|mailboxes| may(mailboxes, &id, |r| r.may_remove_items))
+            })
+        } else {
+            role_id("trash").is_some_and(|id| can_move_to(&id))
+        }
+    };
+
+    let run = move |action: Action| {
+        let thread_ids = thread_ids.get_untracked();
+        let (Some(client), Some(current)) = (
+            state.client.get_untracked(),
+            state.selected_mailbox.get_untracked(),
+        ) else {
+            return;
+        };
+        if thread_ids.is_empty() || busy.get_untracked() {
+            return;
+        }
+        let all_mailboxes = search.get_untracked().1;
+        let on_done = on_done.clone();
+
+        busy.set(true);
+        error_msg.set(None);
+
+        spawn_local(async move {
+            let result = async {
+                let scope = (!all_mailboxes).then_some(current.as_str());
+                let emails = client.get_thread_emails(&thread_ids, scope).await?;
+                if emails.is_empty() {
+                    return Err(JmapError::Api("No messages left to act on".to_string()));
+                }
+                let email_ids: Vec<String> = emails.iter().filter_map(|e| e.id.clone()).collect();
+                match action {
+                    Action::Move(target) if all_mailboxes => {
+                        client.move_emails_from_all(&emails, &target).await
+                    }
+                    Action::Move(target) => client.move_emails(&email_ids, &current, &target).await,
+                    Action::Destroy => client.destroy_emails(&email_ids).await,
+                }
+            }
+            .await;
+
+            busy.set(false);
+            match result {
+                Ok(()) => on_done(),
+                Err(e) => error_msg.set(Some(format!("Failed: {e}"))),
+            }
+        });
+    };
+
+    let run_archive = run.clone();
+    let on_archive = move |_| {
+        if let Some(archive_id) = role_id("archive") {
+            run_archive(Action::Move(archive_id));
+        }
+    };
+
+    let run_delete = run.clone();
+    let on_delete = move |_| {
+        if in_trash() {
+            let confirmed = web_sys::window()
+                .and_then(|w| {
+                    w.confirm_with_message("Permanently delete the selected messages?")
+                        .ok()
+                })
+                .unwrap_or(false);
+            if confirmed {
+                run_delete(Action::Destroy);
+            }
+        } else if let Some(trash_id) = role_id("trash") {
+            run_delete(Action::Move(trash_id));
+        }
+    };
+
+    let on_move = move |ev: leptos::ev::Event| {
+        let select: web_sys::HtmlSelectElement = event_target(&ev);
+        let target = select.value();
+        // Reset to the placeholder so the same target can be picked again
+        select.set_value("");
+        if !target.is_empty() {
+            run(Action::Move(target));
+        }
+    };
+
+    view! {
+        <div class="email-actions">
+            <button
+                on:click=on_archive
+                disabled=move || busy.get() || !can_archive()
+            >"Archive"</button>
+            <button
+                on:click=on_delete
+                disabled=move || busy.get() || !can_delete()
+            >{move || if in_trash() { "Delete permanently" } else { "Delete" }}</button>
+            <select on:change=on_move disabled=move || busy.get()>
+                <option value="">"Move to..."</option>
+                {move || {
+                    let mailboxes = state.mailboxes.get();
+                    flatten_tree(&mailboxes, None, 0)
+                        .into_iter()
+                        .map(|(mailbox, depth)| {
+                            let indent = "\u{a0}\u{a0}".repeat(depth as usize);
+                            let label = format!("{indent}{}", mailbox.name);
+                            let disabled = !can_move_to(&mailbox.id);
+                            view! {
+                                <option value=mailbox.id disabled=disabled>{label}</option>
+                            }
+                        })
+                        .collect_view()
+                }}
+            </select>
+            {move || error_msg.get().map(|msg| view! {
+                <span class="error-message">{msg}</span>
+            })}
+        </div>
+    }
+}
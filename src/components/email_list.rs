@@ -1,9 +1,17 @@
-use crate::router::mailbox_id_to_slug;
+use crate::components::email_actions::EmailActions;
+use crate::cache::{self, CachedList};
+use crate::router::{mailbox_id_to_slug, search_query_string, use_search};
+use crate::search::{has_text_filter, parse_search, snippet_segments};
 use crate::state::AppState;
-use jmap_client::Email;
+use crate::store::set_keyword_optimistic;
+use jmap_client::{
+    Email, EmailFilter, EmailFilterCondition, EmailQuery, EmailSort, EmailSortProperty,
+    SearchSnippet,
+};
 use leptos::prelude::*;
 use leptos::task::spawn_local;
 use leptos_router::hooks::use_navigate;
+use std::collections::{HashMap, HashSet};
 use wasm_bindgen::prelude::Closure;
 use wasm_bindgen::JsCast;
 
@@ -28,6 +36,33 @@ pub fn EmailList() -> impl IntoView {
     let emails: RwSignal<Vec<Email>> = RwSignal::new(vec![]);
     let has_more = RwSignal::new(false);
     let loading = RwSignal::new(false);
+    let search = use_search();
+    let search_error = RwSignal::new(Option::<String>::None);
+    // Highlighted subject/preview by email ID, when searching text
+    let snippets: RwSignal<HashMap<String, SearchSnippet>> = RwSignal::new(HashMap::new());
+    // Thread IDs of rows checked for bulk actions
+    let selected: RwSignal<HashSet<String>> = RwSignal::new(HashSet::new());
+
+    // Query state of the loaded window, for applying changes in place on push
+    let query_state = StoredValue::new(Option::<String>::None);
+
+    // Keep the loaded window in the offline cache (search results aren't
+    // cached)
+    let save_list = move || {
+        if search.with_untracked(|(query, _)| !query.trim().is_empty()) {
+            return;
+        }
+        let (Some(mailbox_id), Some(query_state)) =
+            (state.selected_mailbox.get_untracked(), query_state.get_value())
+        else {
+            return;
+        };
+        let list = CachedList {
+            query_state,
+            emails: emails.get_untracked(),
+        };
+        spawn_local(async move { cache::save(&cache::list_key(&mailbox_id), &list).await });
+    };
 
     let load_page = move |position: u64, append: bool| {
         loading.set(true);
@@ -38,22 +73,66 @@ pub fn EmailList() -> impl IntoView {
                 loading.set(false);
                 return;
             };
-            let (ids, total) = client
-                .query_emails(&mailbox_id, position, PAGE_SIZE)
-                .await
-                .ok()
-                .unwrap_or_default();
-            if ids.is_empty() {
+            let (mut query, want_snippets) = match list_query(state, &mailbox_id, search) {
+                Ok(q) => {
+                    search_error.set(None);
+                    q
+                }
+                Err(e) => {
+                    search_error.set(Some(e));
+                    has_more.set(false);
+                    loading.set(false);
+                    return;
+                }
+            };
+            query.position = position as i64;
+            query.limit = Some(PAGE_SIZE);
+
+            // Query, snippets and email properties in one round trip
+            let mut batch = client.batch();
+            let query_call = match batch.email_query(&query) {
+                Ok(call) => call,
+                Err(e) => {
+                    search_error.set(Some(e.to_string()));
+                    has_more.set(false);
+                    loading.set(false);
+                    return;
+                }
+            };
+            let ids = query_call.reference("/ids");
+            let snippet_call =
+                want_snippets.then(|| batch.search_snippet_get(query.filter.as_ref(), ids.clone()));
+            let get_call = batch.email_get(ids, LIST_PROPERTIES);
+            let Ok(response) = batch.send().await else {
                 has_more.set(false);
                 loading.set(false);
                 return;
+            };
+
+            let query_result = response.get(&query_call).ok();
+            let total = query_result.as_ref().and_then(|r| r.total);
+            if position == 0 {
+                query_state.set_value(query_result.map(|r| r.query_state));
+            }
+            // Snippets are optional; servers without support just fail that call
+            if let Some(new_snippets) = snippet_call.and_then(|call| response.get(&call).ok()) {
+                snippets.update(|map| {
+                    map.extend(new_snippets.list.into_iter().map(|s| (s.email_id.clone(), s)))
+                });
             }
-            let (new_emails, email_state) = client
-                .get_emails(&ids, LIST_PROPERTIES)
-                .await
-                .ok()
-                .unwrap_or_default();
-            state.email_state.set(Some(email_state));
+            let Ok(got) = response.get(&get_call) else {
+                has_more.set(false);
+                loading.set(false);
+                return;
+            };
+            let new_emails = got.list;
+            if new_emails.is_empty() {
+                has_more.set(false);
+                loading.set(false);
+                return;
+            }
+            state.store.merge_emails(&new_emails, &got.state);
+            state.email_state.set(Some(got.state));
 
             let loaded_count = new_emails.len() as u64;
             if append {
@@ -63,23 +142,134 @@ pub fn EmailList() -> impl IntoView {
             }
             // Server only returns total if calculateTotal was requested.
             // Fall back to heuristic: if we got a full page, assume more exist.
-            if total > 0 {
-                has_more.set((position + loaded_count) < total);
-            } else {
-                has_more.set(loaded_count >= PAGE_SIZE);
+            match total {
+                Some(total) => has_more.set((position + loaded_count) < total),
+                None => has_more.set(loaded_count >= PAGE_SIZE),
             }
             loading.set(false);
+            save_list();
         });
     };
 
-    // Reset and load when mailbox or refresh trigger changes
-    Effect::new(move || {
-        let _mailbox = state.selected_mailbox.get();
-        let _refresh = state.email_refresh_trigger.get();
-        let _client = state.client.get();
+    let clear = move || {
+        selected.set(HashSet::new());
         emails.set(vec![]);
+        snippets.set(HashMap::new());
         has_more.set(false);
+        query_state.set_value(None);
+    };
+    let reset = move || {
+        clear();
         load_page(0, false);
+    };
+
+    // Bring the loaded window up to date with Email/queryChanges (keyword
+    // changes on rows that didn't move come through the store). Falls back
+    // to a full reload if the server can't calculate the changes.
+    let apply_changes = move || {
+        let Some(since_query_state) = query_state.get_value() else {
+            reset();
+            return;
+        };
+        spawn_local(async move {
+            let client = state.client.get_untracked();
+            let mailbox_id = state.selected_mailbox.get_untracked();
+            let (Some(client), Some(mailbox_id)) = (client, mailbox_id) else {
+                return;
+            };
+            let Ok((query, _)) = list_query(state, &mailbox_id, search) else {
+                return;
+            };
+            let up_to_id = emails.with_untracked(|list| list.last().and_then(|e| e.id.clone()));
+
+            let mut batch = client.batch();
+            let changes_call =
+                batch.email_query_changes(&query, &since_query_state, up_to_id.as_deref());
+            let added_ids = changes_call.reference("/added/*/id");
+            let added_call = batch.email_get(added_ids, LIST_PROPERTIES);
+            let Ok(response) = batch.send().await else { return };
+            // Another update got there first
+            if query_state.get_value().as_deref() != Some(since_query_state.as_str()) {
+                return;
+            }
+
+            let (Ok(changes), Ok(added)) = (response.get(&changes_call), response.get(&added_call))
+            else {
+                reset();
+                return;
+            };
+            state.store.merge_emails(&added.list, &added.state);
+
+            let mut added_items = changes.added;
+            added_items.sort_by_key(|item| item.index);
+            emails.update(|list| {
+                list.retain(|e| !e.id.as_ref().is_some_and(|id| changes.removed.contains(id)));
+                for item in &added_items {
+                    let index = item.index as usize;
+                    // Past the loaded window: picked up by the next page load
+                    if index > list.len() {
+                        continue;
+                    }
+                    let email = added.list.iter().find(|e| e.id.as_ref() == Some(&item.id));
+                    if let Some(email) = email {
+                        list.insert(index, email.clone());
+                    }
+                }
+            });
+            selected.update(|sel| {
+                emails.with_untracked(|list| {
+                    sel.retain(|t| list.iter().any(|e| e.thread_id.as_ref() == Some(t)))
+                })
+            });
+
+            query_state.set_value(Some(changes.new_query_state));
+            save_list();
+        });
+    };
+
+    // Show the cached window straight away and bring it up to date with
+    // queryChanges, or load from scratch if there's nothing cached
+    let reload = move || {
+        clear();
+        let searching = search.with_untracked(|(query, _)| !query.trim().is_empty());
+        let Some(mailbox_id) = state.selected_mailbox.get_untracked().filter(|_| !searching)
+        else {
+            load_page(0, false);
+            return;
+        };
+        spawn_local(async move {
+            let cached = cache::load::<CachedList>(&cache::list_key(&mailbox_id)).await;
+            // The mailbox may have changed while reading
+            if state.selected_mailbox.get_untracked().as_deref() != Some(mailbox_id.as_str()) {
+                return;
+            }
+            match cached {
+                Some(list) => {
+                    state.store.add_missing(&list.emails);
+                    has_more.set(list.emails.len() as u64 >= PAGE_SIZE);
+                    emails.set(list.emails);
+                    query_state.set_value(Some(list.query_state));
+                    apply_changes();
+                }
+                None => load_page(0, false),
+            }
+        });
+    };
+
+    // Reset and load when the mailbox or search changes, or on (re)connect
+    Effect::new(move || {
+        let _mailbox = state.selected_mailbox.get();
+        let _search = search.get();
+        let _client = state.client.get();
+        reload();
+    });
+
+    // Email pushes update the list in place, keeping scroll position
+    Effect::new(move |prev: Option<()>| {
+        let _refresh = state.email_refresh_trigger.get();
+        if prev.is_some() {
+            apply_changes();
+        }
     });
 
     // IntersectionObserver for infinite scroll: fires when the sentinel
@@ -106,17 +296,37 @@ pub fn EmailList() -> impl IntoView {
         if let Ok(observer) =
             web_sys::IntersectionObserver::new(callback.as_ref().unchecked_ref())
         {
-            let _ = observer.observe(&el);
+            observer.observe(&el);
         }
         callback.forget();
     });
 
+    let selected_ids = Signal::derive(move || selected.with(|s| s.iter().cloned().collect::<Vec<_>>()));
+    // Drop acted-on threads from the list straight away rather than waiting
+    // for the push-triggered reload
+    let on_actions_done = move || {
+        let done = selected.get_untracked();
+        emails.update(|list| {
+            list.retain(|e| !e.thread_id.as_ref().is_some_and(|t| done.contains(t)))
+        });
+        selected.set(HashSet::new());
+    };
+
     view! {
         <div class="email-list">
+            {move || (!selected.with(|s| s.is_empty())).then(|| view! {
+                <div class="email-list-toolbar">
+                    <span class="selection-count">
+                        {move || format!("{} selected", selected.with(|s| s.len()))}
+                    </span>
+                    <EmailActions thread_ids=selected_ids on_done=on_actions_done/>
+                </div>
+            })}
             <For
                 each=move || emails.get()
                 key=|email| email.id.clone().unwrap_or_default()
                 children=move |email| {
+                    let email_id = email.id.clone().unwrap_or_default();
                     let thread_id = email.thread_id.clone().unwrap_or_default();
                     let subject = email.subject.clone().unwrap_or_else(|| "(no subject)".to_string());
                     let preview = email.preview.clone().unwrap_or_default();
@@ -125,42 +335,129 @@ pub fn EmailList() -> impl IntoView {
                         .map(|a| a.name.as_deref().unwrap_or(&a.email).to_string())
                         .unwrap_or_else(|| "(unknown)".to_string());
                     let date = email.received_at.clone().unwrap_or_default();
-                    let is_unread = !email.keywords.as_ref().map_or(false, |kw| kw.contains_key("$seen"));
+                    let is_unread = {
+                        let email_id = email_id.clone();
+                        Memo::new(move |_| !state.store.has_keyword(&email_id, "$seen"))
+                    };
+                    let is_flagged = {
+                        let email_id = email_id.clone();
+                        Memo::new(move |_| state.store.has_keyword(&email_id, "$flagged"))
+                    };
                     let has_attachment = email.has_attachment.unwrap_or(false);
                     let nav = navigate.clone();
 
+                    // Drafts open in the compose view for editing
                     let on_click = {
                         let thread_id = thread_id.clone();
+                        let email_id = email_id.clone();
                         move |_| {
                             let mailboxes = state.mailboxes.get();
-                            let slug = state
-                                .selected_mailbox
-                                .get()
+                            let selected = state.selected_mailbox.get();
+                            let slug = selected
                                 .as_deref()
                                 .map(|id| mailbox_id_to_slug(&mailboxes, id))
                                 .unwrap_or_else(|| "inbox".to_string());
+                            let in_drafts = mailboxes.iter().any(|m| {
+                                Some(&m.id) == selected.as_ref()
+                                    && m.role.as_deref() == Some("drafts")
+                            });
+                            if in_drafts {
+                                let draft = String::from(js_sys::encode_uri_component(&email_id));
+                                nav(
+                                    &format!("/mail/{slug}/compose?draft={draft}"),
+                                    Default::default(),
+                                );
+                                return;
+                            }
+                            let (query, all_mailboxes) = search.get_untracked();
+                            let suffix = search_query_string(&query, all_mailboxes);
                             nav(
-                                &format!("/mail/{slug}/{}", thread_id),
+                                &format!("/mail/{slug}/{}{suffix}", thread_id),
                                 Default::default(),
                             );
                         }
                     };
 
+                    let on_toggle_seen = {
+                        let email_id = email_id.clone();
+                        move |ev: leptos::ev::MouseEvent| {
+                            ev.stop_propagation();
+                            let enabled = is_unread.get_untracked();
+                            set_keyword_optimistic(state, vec![email_id.clone()], "$seen", enabled);
+                        }
+                    };
+                    let on_toggle_flag = {
+                        let email_id = email_id.clone();
+                        move |ev: leptos::ev::MouseEvent| {
+                            ev.stop_propagation();
+                            let enabled = !is_flagged.get_untracked();
+                            let ids = vec![email_id.clone()];
+                            set_keyword_optimistic(state, ids, "$flagged", enabled);
+                        }
+                    };
+
+                    let is_selected = {
+                        let thread_id = thread_id.clone();
+                        Memo::new(move |_| selected.with(|s| s.contains(&thread_id)))
+                    };
+                    let on_toggle_select = {
+                        let thread_id = thread_id.clone();
+                        move |ev: leptos::ev::MouseEvent| {
+                            ev.stop_propagation();
+                            let thread_id = thread_id.clone();
+                            selected.update(|s| {
+                                if !s.remove(&thread_id) {
+                                    s.insert(thread_id);
+                                }
+                            });
+                        }
+                    };
+
                     view! {
                         <div
                             class="email-row"
                             class:unread=is_unread
+                            class:selected=is_selected
                             on:click=on_click
                         >
+                            <div class="email-row-actions">
+                                <input
+                                    type="checkbox"
+                                    prop:checked=is_selected
+                                    on:click=on_toggle_select
+                                />
+                                <button
+                                    class="flag-toggle"
+                                    class:flagged=is_flagged
+                                    title=move || if is_flagged.get() { "Unflag" } else { "Flag" }
+                                    on:click=on_toggle_flag
+                                >"★"</button>
+                                <button
+                                    class="seen-toggle"
+                                    title=move || if is_unread.get() { "Mark as read" } else { "Mark as unread" }
+                                    on:click=on_toggle_seen
+                                >{move || if is_unread.get() { "●" } else { "○" }}</button>
+                            </div>
                             <div class="email-from">{from}</div>
                             <div class="email-subject-preview">
-                                <span class="email-subject">{subject}</span>
+                                <span class="email-subject">
+                                    {
+                                        let email_id = email_id.clone();
+                                        move || highlighted(snippets, &email_id, |s| s.subject.clone(), &subject)
+                                    }
+                                </span>
                                 {if has_attachment {
                                     Some(view! { <span class="attachment-icon">" [att]"</span> })
                                 } else {
                                     None
                                 }}
-                                <span class="email-preview">" - " {preview}</span>
+                                <span class="email-preview">
+                                    " - "
+                                    {
+                                        let email_id = email_id.clone();
+                                        move || highlighted(snippets, &email_id, |s| s.preview.clone(), &preview)
+                                    }
+                                </span>
                             </div>
                             <div class="email-date">{format_date(&date)}</div>
                         </div>
@@ -170,12 +467,17 @@ pub fn EmailList() -> impl IntoView {
             {move || {
                 let list_empty = emails.with(|l| l.is_empty());
                 let is_loading = loading.get();
-                if list_empty && is_loading {
-                    Some(view! { <div class="loading">"Loading..."</div> })
+                let searching = search.with(|(query, _)| !query.trim().is_empty());
+                if let Some(err) = search_error.get() {
+                    Some(view! { <div class="error-message">{err}</div> }.into_any())
+                } else if list_empty && is_loading {
+                    Some(view! { <div class="loading">"Loading..."</div> }.into_any())
+                } else if list_empty && searching {
+                    Some(view! { <div class="empty">"No matching emails"</div> }.into_any())
                 } else if list_empty {
-                    Some(view! { <div class="empty">"No emails in this mailbox"</div> })
+                    Some(view! { <div class="empty">"No emails in this mailbox"</div> }.into_any())
                 } else if is_loading {
-                    Some(view! { <div class="loading-more">"Loading more..."</div> })
+                    Some(view! { <div class="loading-more">"Loading more..."</div> }.into_any())
                 } else {
                     None
                 }
@@ -185,6 +487,81 @@ pub fn EmailList() -> impl IntoView {
     }
 }
 
+/// The list's Email/query for the selected mailbox and current search,
+/// without paging, and whether search snippets are wanted.
+fn list_query(
+    state: AppState,
+    mailbox_id: &str,
+    search: Memo<(String, bool)>,
+) -> Result<(EmailQuery, bool), String> {
+    let (query, all_mailboxes) = search.get_untracked();
+    let search_filter = parse_search(&query)?;
+    let want_snippets = search_filter.as_ref().is_some_and(has_text_filter);
+    let query = EmailQuery {
+        filter: Some(build_filter(state, mailbox_id, search_filter, all_mailboxes)),
+        sort: vec![EmailSort::descending(EmailSortProperty::ReceivedAt)],
+        collapse_threads: true,
+        ..Default::default()
+    };
+    Ok((query, want_snippets))
+}
+
+/// Combine the search with the mailbox scope. Searching all mailboxes
+/// leaves out Trash and Junk, as most mail clients do.
+fn build_filter(
+    state: AppState,
+    mailbox_id: &str,
+    search: Option<EmailFilter>,
+    all_mailboxes: bool,
+) -> EmailFilter {
+    let Some(search) = search else {
+        return EmailFilter::in_mailbox(mailbox_id);
+    };
+    if !all_mailboxes {
+        return EmailFilter::and(vec![EmailFilter::in_mailbox(mailbox_id), search]);
+    }
+
+    let excluded: Vec<String> = state.mailboxes.with_untracked(|mailboxes| {
+        mailboxes
+            .iter()
+            .filter(|m| matches!(m.role.as_deref(), Some("trash" | "junk")))
+            .map(|m| m.id.clone())
+            .collect()
+    });
+    if excluded.is_empty() {
+        return search;
+    }
+    let scope = EmailFilterCondition {
+        in_mailbox_other_than: Some(excluded),
+        ..Default::default()
+    };
+    EmailFilter::and(vec![scope.into(), search])
+}
+
+/// Render a snippet's highlighted runs if there is one, otherwise the
+/// plain fallback text.
+fn highlighted(
+    snippets: RwSignal<HashMap<String, SearchSnippet>>,
+    email_id: &str,
+    field: fn(&SearchSnippet) -> Option<String>,
+    fallback: &str,
+) -> AnyView {
+    let Some(snippet) = snippets.with(|map| map.get(email_id).and_then(field)) else {
+        return fallback.to_string().into_any();
+    };
+    snippet_segments(&snippet)
+        .into_iter()
+        .map(|(text, is_match)| {
+            if is_match {
+                view! { <mark>{text}</mark> }.into_any()
+            } else {
+                text.into_any()
+            }
+        })
+        .collect_view()
+        .into_any()
+}
+
 fn format_date(date_str: &str) -> String {
     if let Some(t_pos) = date_str.find('T') {
         date_str[..t_pos].to_string()
@@ -0,0 +1,256 @@
+use crate::components::compose::parse_addresses;
+use crate::state::AppState;
+use crate::sync::sync_identities;
+use jmap_client::{EmailAddress, Identity, IdentityProperties};
+use leptos::prelude::*;
+use leptos::task::spawn_local;
+
+/// Which identity the editor is open for, if any.
+#[derive(Clone, PartialEq)]
+enum Editing {
+    New,
+    Existing(String),
+}
+
+/// Settings screen for the account's identities: their names, Reply-To,
+/// Bcc and signatures. Kept current by `sync_identities` as push changes
+/// arrive.
+#[component]
+pub fn IdentitySettings() -> impl IntoView {
+    let state = use_context::<AppState>().expect("AppState to be provided");
+    let editing = RwSignal::new(Option::<Editing>::None);
+    let error_msg = RwSignal::new(Option::<String>::None);
+
+    let on_done = move || editing.set(None);
+
+    let on_delete = move |identity: Identity| {
+        let message = format!("Delete the identity for {}?", identity.email);
+        let confirmed = web_sys::window()
+            .and_then(|w| w.confirm_with_message(&message).ok())
+            .unwrap_or(false);
+        if !confirmed {
+            return;
+        }
+        let Some(client) = state.client.get_untracked() else {
+            error_msg.set(Some("Not connected".to_string()));
+            return;
+        };
+        error_msg.set(None);
+        spawn_local(async move {
+            match client.destroy_identity(&identity.id).await {
+                Ok(()) => sync_identities(state).await,
+                Err(e) => error_msg.set(Some(format!("Couldn't delete identity: {e}"))),
+            }
+        });
+    };
+
+    view! {
+        <div class="identity-settings">
+            <div class="email-list-toolbar">
+                <span class="identity-settings-title">"Identities"</span>
+                <button
+                    class="identity-new-btn"
+                    disabled=move || editing.get().is_some()
+                    on:click=move |_| editing.set(Some(Editing::New))
+                >"New identity"</button>
+            </div>
+            {move || error_msg.get().map(|msg| view! {
+                <div class="error-message identity-error" on:click=move |_| error_msg.set(None)>
+                    {msg}
+                </div>
+            })}
+            // Only rebuilt when editing starts or stops, so changes pushed
+            // from elsewhere don't discard the edits in progress
+            {move || {
+                let identity = match editing.get()? {
+                    Editing::New => None,
+                    Editing::Existing(id) => Some(state.identities.with_untracked(|identities| {
+                        identities.iter().find(|i| i.id == id).cloned()
+                    })?),
+                };
+                Some(view! { <IdentityEditor identity on_done/> })
+            }}
+            {move || {
+                let identities = state.identities.get();
+                if identities.is_empty() && editing.get().is_none() {
+                    return view! { <div class="empty">"No identities"</div> }.into_any();
+                }
+                identities.into_iter().map(|identity| {
+                    let id = identity.id.clone();
+                    let is_editing = Memo::new(move |_| {
+                        editing.with(|e| matches!(e, Some(Editing::Existing(e)) if *e == id))
+                    });
+                    let id = identity.id.clone();
+                    let on_edit = move |_| editing.set(Some(Editing::Existing(id.clone())));
+                    let name = identity.name.clone().filter(|n| !n.is_empty());
+                    let signature = identity
+                        .text_signature
+                        .clone()
+                        .filter(|s| !s.is_empty())
+                        .unwrap_or_else(|| "No signature".to_string());
+                    let may_delete = identity.may_delete == Some(true);
+                    view! {
+                        <div class="identity" class:editing=is_editing>
+                            <div class="identity-header">
+                                <span class="identity-name">
+                                    {name.unwrap_or_else(|| identity.email.clone())}
+                                </span>
+                                <span class="identity-email">{identity.email.clone()}</span>
+                                <button
+                                    class="identity-btn"
+                                    disabled=move || editing.get().is_some()
+                                    on:click=on_edit
+                                >"Edit"</button>
+                                {may_delete.then(|| view! {
+                                    <button
+                                        class="identity-btn"
+                                        disabled=move || editing.get().is_some()
+                                        on:click=move |_| on_delete(identity.clone())
+                                    >"Delete"</button>
+                                })}
+                            </div>
+                            <pre class="identity-signature">{signature}</pre>
+                        </div>
+                    }.into_any()
+                }).collect_view().into_any()
+            }}
+        </div>
+    }
+}
+
+/// Form for a new identity, or for editing `identity`. The address can only
+/// be chosen when creating.
+#[component]
+fn IdentityEditor(
+    identity: Option<Identity>,
+    on_done: impl Fn() + Copy + Send + Sync + 'static,
+) -> impl IntoView {
+    let state = use_context::<AppState>().expect("AppState to be provided");
+    let original = identity.as_ref().map(IdentityProperties::from).unwrap_or_default();
+    let identity_id = identity.as_ref().map(|i| i.id.clone());
+    let is_new = identity_id.is_none();
+
+    let email = RwSignal::new(identity.as_ref().map(|i| i.email.clone()).unwrap_or_default());
+    let name = RwSignal::new(original.name.clone());
+    let reply_to = RwSignal::new(join_addresses(original.reply_to.as_deref()));
+    let bcc = RwSignal::new(join_addresses(original.bcc.as_deref()));
+    let text_signature = RwSignal::new(original.text_signature.clone());
+    let html_signature = RwSignal::new(original.html_signature.clone());
+    let busy = RwSignal::new(false);
+    let error_msg = RwSignal::new(Option::<String>::None);
+    let original = StoredValue::new(original);
+
+    let on_submit = move |ev: leptos::ev::SubmitEvent| {
+        ev.prevent_default();
+        if busy.get_untracked() {
+            return;
+        }
+        let address = email.get_untracked().trim().to_string();
+        if is_new && address.is_empty() {
+            error_msg.set(Some("Enter an email address".to_string()));
+            return;
+        }
+        let Some(client) = state.client.get_untracked() else {
+            error_msg.set(Some("Not connected".to_string()));
+            return;
+        };
+        let properties = original.with_value(|original| IdentityProperties {
+            name: name.get_untracked().trim().to_string(),
+            reply_to: edited_addresses(&reply_to.get_untracked(), original.reply_to.as_deref()),
+            bcc: edited_addresses(&bcc.get_untracked(), original.bcc.as_deref()),
+            text_signature: text_signature.get_untracked(),
+            html_signature: html_signature.get_untracked(),
+        });
+        let identity_id = identity_id.clone();
+
+        busy.set(true);
+        error_msg.set(None);
+        spawn_local(async move {
+            let result = match &identity_id {
+                Some(id) => client.update_identity(id, &properties).await,
+                None => client.create_identity(&address, &properties).await.map(|_| ()),
+            };
+            match result {
+                Ok(()) => {
+                    sync_identities(state).await;
+                    on_done();
+                }
+                Err(e) => {
+                    error_msg.set(Some(format!("Couldn't save identity: {e}")));
+                    busy.set(false);
+                }
+            }
+        });
+    };
+
+    view! {
+        <form class="compose-form identity-editor" on:submit=on_submit>
+            <div class="form-field">
+                <label>"Email"</label>
+                <input
+                    type="email"
+                    bind:value=email
+                    disabled=!is_new
+                    placeholder="you@example.com"
+                />
+            </div>
+            <div class="form-field">
+                <label>"Name"</label>
+                <input type="text" bind:value=name/>
+            </div>
+            <div class="form-field">
+                <label>"Reply-To"</label>
+                <input type="text" bind:value=reply_to/>
+            </div>
+            <div class="form-field">
+                <label>"Bcc"</label>
+                <input type="text" bind:value=bcc/>
+            </div>
+            <div class="form-field">
+                <label>"Signature"</label>
+                <textarea rows="5" bind:value=text_signature></textarea>
+            </div>
+            <div class="form-field">
+                <label>"HTML signature"</label>
+                <textarea rows="5" bind:value=html_signature></textarea>
+            </div>
+            {move || error_msg.get().map(|msg| view! {
+                <div class="error-message">{msg}</div>
+            })}
+            <div class="compose-actions">
+                <button type="submit" disabled=move || busy.get()>
+                    {move || if busy.get() { "Saving..." } else { "Save" }}
+                </button>
+                <button type="button" disabled=move || busy.get() on:click=move |_| on_done()>
+                    "Cancel"
+                </button>
+            </div>
+        </form>
+    }
+}
+
+fn join_addresses(addresses: Option<&[EmailAddress]>) -> String {
+    addresses
+        .unwrap_or_default()
+        .iter()
+        .map(|a| a.email.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Parse an edited address field, keeping the display names of addresses
+/// that were already there. An empty field clears the property.
+fn edited_addresses(field: &str, original: Option<&[EmailAddress]>) -> Option<Vec<EmailAddress>> {
+    let original = original.unwrap_or_default();
+    let addresses: Vec<EmailAddress> = parse_addresses(field)
+        .into_iter()
+        .map(|address| {
+            original
+                .iter()
+                .find(|o| o.email.eq_ignore_ascii_case(&address.email))
+                .cloned()
+                .unwrap_or(address)
+        })
+        .collect();
+    (!addresses.is_empty()).then_some(addresses)
+}
@@ -1,8 +1,11 @@
 use crate::router::mailbox_id_to_slug;
-use crate::state::AppState;
-use jmap_client::Mailbox;
+use crate::state::{AppState, ReplyMode};
+use crate::sync::reload_mailboxes;
+use jmap_client::{JmapError, Mailbox};
 use leptos::prelude::*;
-use leptos_router::hooks::use_navigate;
+use leptos::task::spawn_local;
+use leptos_router::hooks::{use_location, use_navigate};
+use std::collections::HashSet;
 
 fn role_sort_order(role: Option<&str>) -> u32 {
     match role {
@@ -17,7 +20,7 @@ fn role_sort_order(role: Option<&str>) -> u32 {
 }
 
 /// Flatten the mailbox tree into a sorted list with depth info.
-fn flatten_tree(mailboxes: &[Mailbox], parent_id: Option<&str>, depth: u32) -> Vec<(Mailbox, u32)> {
+pub fn flatten_tree(mailboxes: &[Mailbox], parent_id: Option<&str>, depth: u32) -> Vec<(Mailbox, u32)> {
     let mut children: Vec<&Mailbox> = mailboxes
         .iter()
         .filter(|m| m.parent_id.as_deref() == parent_id)
@@ -38,19 +41,178 @@ fn flatten_tree(mailboxes: &[Mailbox], parent_id: Option<&str>, depth: u32) -> V
     result
 }
 
+/// IDs of all mailboxes below `mailbox_id` in the tree.
+fn descendants(mailboxes: &[Mailbox], mailbox_id: &str) -> HashSet<String> {
+    let mut result = HashSet::new();
+    let mut pending = vec![mailbox_id.to_string()];
+    while let Some(parent) = pending.pop() {
+        for child in mailboxes.iter().filter(|m| m.parent_id.as_deref() == Some(&parent)) {
+            if result.insert(child.id.clone()) {
+                pending.push(child.id.clone());
+            }
+        }
+    }
+    result
+}
+
+/// Turn Mailbox/set errors into something a user can act on.
+fn mailbox_error_message(e: &JmapError) -> String {
+    match e {
+        JmapError::MethodError { type_, description } => match type_.as_str() {
+            "mailboxHasChild" => {
+                "This folder has subfolders. Delete or move them first.".to_string()
+            }
+            "mailboxHasEmail" => "This folder still contains messages.".to_string(),
+            "forbidden" => "You don't have permission to do that.".to_string(),
+            "invalidProperties" => description
+                .clone()
+                .unwrap_or_else(|| "Invalid folder name or location.".to_string()),
+            _ => e.to_string(),
+        },
+        _ => e.to_string(),
+    }
+}
+
+fn prompt(message: &str, default: &str) -> Option<String> {
+    web_sys::window()?
+        .prompt_with_message_and_default(message, default)
+        .ok()
+        .flatten()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+}
+
+fn confirm(message: &str) -> bool {
+    web_sys::window()
+        .and_then(|w| w.confirm_with_message(message).ok())
+        .unwrap_or(false)
+}
+
+enum MailboxOp {
+    Create { name: String, parent_id: Option<String> },
+    Rename { id: String, name: String },
+    Move { id: String, parent_id: Option<String> },
+    Destroy { id: String, remove_emails: bool },
+}
+
 #[component]
 pub fn MailboxSidebar() -> impl IntoView {
     let state = use_context::<AppState>().expect("AppState to be provided");
     let navigate = use_navigate();
+    let location = use_location();
+    let in_outbox = Memo::new(move |_| location.pathname.get().starts_with("/outbox"));
+    let in_sending = Memo::new(move |_| location.pathname.get().starts_with("/sending"));
+    let in_mailbox = Memo::new(move |_| location.pathname.get().starts_with("/mail/"));
+
+    // Mailbox whose action menu is open
+    let menu_open = RwSignal::new(Option::<String>::None);
+    let busy = RwSignal::new(false);
+    let error_msg = RwSignal::new(Option::<String>::None);
+
+    let nav = navigate.clone();
+    let run = move |op: MailboxOp| {
+        let Some(client) = state.client.get_untracked() else { return };
+        if busy.get_untracked() {
+            return;
+        }
+        let nav = nav.clone();
+        busy.set(true);
+        error_msg.set(None);
+        spawn_local(async move {
+            let destroyed = match &op {
+                MailboxOp::Destroy { id, .. } => Some(id.clone()),
+                _ => None,
+            };
+            let result = match op {
+                MailboxOp::Create { name, parent_id } => client
+                    .create_mailbox(&name, parent_id.as_deref())
+                    .await
+                    .map(|_| ()),
+                MailboxOp::Rename { id, name } => client.rename_mailbox(&id, &name).await,
+                MailboxOp::Move { id, parent_id } => {
+                    client.move_mailbox(&id, parent_id.as_deref()).await
+                }
+                MailboxOp::Destroy { id, remove_emails } => {
+                    client.destroy_mailbox(&id, remove_emails).await
+                }
+            };
+            busy.set(false);
+            match result {
+                Ok(()) => {
+                    menu_open.set(None);
+                    if destroyed.is_some() && state.selected_mailbox.get_untracked() == destroyed {
+                        nav("/mail/inbox", Default::default());
+                    }
+                    reload_mailboxes(state).await;
+                }
+                Err(e) => error_msg.set(Some(mailbox_error_message(&e))),
+            }
+        });
+    };
+
+    let may_create_top_level = state
+        .client
+        .get_untracked()
+        .and_then(|c| c.mail_capabilities())
+        .is_none_or(|m| m.may_create_top_level_mailbox);
+
+    let run_create = run.clone();
+    let on_new_folder = move |_| {
+        if let Some(name) = prompt("New folder name", "") {
+            run_create(MailboxOp::Create {
+                name,
+                parent_id: None,
+            });
+        }
+    };
+
+    let nav = navigate.clone();
+    let open_virtual = move |path: &'static str| {
+        state.reply_to_email.set(None);
+        state.reply_mode.set(ReplyMode::Reply);
+        nav(path, Default::default());
+    };
+    let open_outbox = open_virtual.clone();
+    let on_outbox = move |_| open_outbox("/outbox");
+    let on_sending = move |_| open_virtual("/sending");
 
     view! {
         <div class="mailbox-list">
+            {move || error_msg.get().map(|msg| view! {
+                <div class="error-message mailbox-error" on:click=move |_| error_msg.set(None)>
+                    {msg}
+                </div>
+            })}
+            // Virtual folder, only shown while something is waiting to send
+            {move || {
+                let queued = state.outbox.with(|outbox| outbox.len());
+                (queued > 0).then(|| view! {
+                    <div
+                        class="mailbox-item outbox-item"
+                        class:active=move || in_outbox.get()
+                        style:padding-left="8px"
+                        on:click=on_outbox.clone()
+                    >
+                        <span class="mailbox-name">"Outbox"</span>
+                        <span class="unread-badge">{queued}</span>
+                    </div>
+                })
+            }}
+            <div
+                class="mailbox-item sending-item"
+                class:active=move || in_sending.get()
+                style:padding-left="8px"
+                on:click=on_sending
+            >
+                <span class="mailbox-name">"Sending"</span>
+            </div>
             {move || {
                 let mailboxes = state.mailboxes.get();
                 let flat = flatten_tree(&mailboxes, None, 0);
                 flat.into_iter().map(|(mailbox, depth)| {
                     let mailbox_id = mailbox.id.clone();
                     let mailbox_id_click = mailbox_id.clone();
+                    let mailbox_id_menu = mailbox_id.clone();
                     let mailbox_name = mailbox.name.clone();
                     let unread = mailbox.unread_emails;
                     let padding_left = format!("{}px", depth * 16 + 8);
@@ -60,14 +222,49 @@ pub fn MailboxSidebar() -> impl IntoView {
                         let mailboxes = state.mailboxes.get();
                         let slug = mailbox_id_to_slug(&mailboxes, &mailbox_id_click);
                         state.reply_to_email.set(None);
-                        state.reply_all.set(false);
+                        state.reply_mode.set(ReplyMode::Reply);
                         nav(&format!("/mail/{slug}"), Default::default());
                     };
 
+                    let on_toggle_menu = {
+                        let mailbox_id = mailbox_id.clone();
+                        move |ev: leptos::ev::MouseEvent| {
+                            ev.stop_propagation();
+                            menu_open.update(|open| {
+                                *open = if open.as_deref() == Some(&mailbox_id) {
+                                    None
+                                } else {
+                                    Some(mailbox_id.clone())
+                                };
+                            });
+                        }
+                    };
+
+                    let menu = {
+                        let run = run.clone();
+                        let mailboxes = mailboxes.clone();
+                        move || {
+                            (menu_open.get().as_deref() == Some(&mailbox_id_menu)).then(|| {
+                                view! {
+                                    <MailboxMenu
+                                        mailbox=mailbox.clone()
+                                        mailboxes=mailboxes.clone()
+                                        depth=depth
+                                        may_create_top_level=may_create_top_level
+                                        run=run.clone()
+                                    />
+                                }
+                            })
+                        }
+                    };
+
                     view! {
                         <div
                             class="mailbox-item"
-                            class:active=move || state.selected_mailbox.get().as_deref() == Some(&mailbox_id)
+                            class:active=move || {
+                                in_mailbox.get()
+                                    && state.selected_mailbox.get().as_deref() == Some(&mailbox_id)
+                            }
                             style:padding-left=padding_left
                             on:click=on_click
                         >
@@ -77,10 +274,147 @@ pub fn MailboxSidebar() -> impl IntoView {
                             } else {
                                 None
                             }}
+                            <button class="mailbox-menu-btn" title="Folder actions" on:click=on_toggle_menu>
+                                "⋯"
+                            </button>
                         </div>
+                        {menu}
                     }
                 }).collect_view()
             }}
+            {may_create_top_level.then(|| view! {
+                <button
+                    class="new-folder-btn"
+                    disabled=move || busy.get()
+                    on:click=on_new_folder
+                >"+ New folder"</button>
+            })}
+        </div>
+    }
+}
+
+/// Context actions for a single mailbox, limited by its `myRights`.
+#[component]
+fn MailboxMenu(
+    mailbox: Mailbox,
+    mailboxes: Vec<Mailbox>,
+    depth: u32,
+    /// From the mail capability; without it nothing can move to the top.
+    may_create_top_level: bool,
+    run: impl Fn(MailboxOp) + Clone + 'static,
+) -> impl IntoView {
+    let rights = mailbox.my_rights.clone();
+    let may_create_child = rights.as_ref().is_none_or(|r| r.may_create_child);
+    let may_rename = rights.as_ref().is_none_or(|r| r.may_rename);
+    let may_delete = rights.as_ref().is_none_or(|r| r.may_delete);
+    let padding_left = format!("{}px", depth * 16 + 20);
+
+    // A mailbox can't move under itself or one of its own descendants, nor
+    // under one that doesn't allow children (unless it's already there)
+    let current_parent = mailbox.parent_id.clone().unwrap_or_default();
+    let excluded = descendants(&mailboxes, &mailbox.id);
+    let parent_options: Vec<(Mailbox, u32)> = flatten_tree(&mailboxes, None, 0)
+        .into_iter()
+        .filter(|(m, _)| m.id != mailbox.id && !excluded.contains(&m.id))
+        .filter(|(m, _)| {
+            m.id == current_parent || m.my_rights.as_ref().is_none_or(|r| r.may_create_child)
+        })
+        .collect();
+
+    let on_new_child = {
+        let run = run.clone();
+        let parent_id = mailbox.id.clone();
+        move |_| {
+            if let Some(name) = prompt("New subfolder name", "") {
+                run(MailboxOp::Create {
+                    name,
+                    parent_id: Some(parent_id.clone()),
+                });
+            }
+        }
+    };
+
+    let on_rename = {
+        let run = run.clone();
+        let id = mailbox.id.clone();
+        let current_name = mailbox.name.clone();
+        move |_| {
+            if let Some(name) = prompt("Rename folder", &current_name)
+                && name != current_name
+            {
+                run(MailboxOp::Rename {
+                    id: id.clone(),
+                    name,
+                });
+            }
+        }
+    };
+
+    let on_move = {
+        let run = run.clone();
+        let id = mailbox.id.clone();
+        let current_parent = current_parent.clone();
+        move |ev: leptos::ev::Event| {
+            let select: web_sys::HtmlSelectElement = event_target(&ev);
+            let target = select.value();
+            select.set_value(&current_parent);
+            if target != current_parent {
+                run(MailboxOp::Move {
+                    id: id.clone(),
+                    parent_id: (!target.is_empty()).then_some(target),
+                });
+            }
+        }
+    };
+
+    let on_delete = {
+        let id = mailbox.id.clone();
+        let name = mailbox.name.clone();
+        let total = mailbox.total_emails;
+        move |_| {
+            let message = if total > 0 {
+                format!(
+                    "Delete folder \"{name}\"? Its {total} messages will be removed from it, \
+                     and any that aren't in another folder will be permanently deleted."
+                )
+            } else {
+                format!("Delete folder \"{name}\"?")
+            };
+            if confirm(&message) {
+                run(MailboxOp::Destroy {
+                    id: id.clone(),
+                    remove_emails: total > 0,
+                });
+            }
+        }
+    };
+
+    // Still listed for a mailbox already at the top, as its current place
+    let offer_top_level = may_create_top_level || current_parent.is_empty();
+
+    view! {
+        <div class="mailbox-menu" style:padding-left=padding_left>
+            {may_create_child.then(|| view! {
+                <button on:click=on_new_child>"New subfolder"</button>
+            })}
+            {may_rename.then(|| view! {
+                <button on:click=on_rename>"Rename"</button>
+                <select title="Move under" on:change=on_move>
+                    {offer_top_level.then(|| view! {
+                        <option value="" selected=current_parent.is_empty()>"(top level)"</option>
+                    })}
+                    {parent_options.into_iter().map(|(m, depth)| {
+                        let indent = "\u{a0}\u{a0}".repeat(depth as usize);
+                        let selected = m.id == current_parent;
+                        view! {
+                            <option value=m.id selected=selected>{format!("{indent}{}", m.name)}</option>
+                        }
+                    }).collect_view()}
+                </select>
+            })}
+            {may_delete.then(|| view! {
+                <button on:click=on_delete>"Delete"</button>
+            })}
         </div>
     }
 }
@@ -1,4 +1,9 @@
 pub mod compose;
+pub mod email_actions;
 pub mod email_list;
+pub mod identities;
 pub mod mailbox_sidebar;
+pub mod outbox;
+pub mod sending;
 pub mod thread_view;
+pub mod undo_send;
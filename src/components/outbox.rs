@@ -0,0 +1,78 @@
+use crate::format::format_timestamp;
+use crate::outbox::{flush_outbox, remove_entry};
+use crate::state::AppState;
+use leptos::prelude::*;
+use leptos::task::spawn_local;
+
+/// Messages queued while offline, with their last send error if any.
+#[component]
+pub fn OutboxView() -> impl IntoView {
+    let state = use_context::<AppState>().expect("AppState to be provided");
+    let retrying = RwSignal::new(false);
+
+    let on_retry = move |_| {
+        retrying.set(true);
+        spawn_local(async move {
+            flush_outbox(state).await;
+            retrying.set(false);
+        });
+    };
+
+    view! {
+        <div class="outbox">
+            <div class="email-list-toolbar">
+                <span class="outbox-title">"Outbox"</span>
+                <button
+                    class="outbox-retry-btn"
+                    disabled=move || retrying.get() || state.client.get().is_none()
+                    on:click=on_retry
+                >
+                    {move || if retrying.get() { "Sending..." } else { "Retry now" }}
+                </button>
+            </div>
+            {move || {
+                let entries = state.outbox.get();
+                if entries.is_empty() {
+                    return view! { <div class="empty">"Outbox is empty"</div> }.into_any();
+                }
+                entries.into_iter().map(|entry| {
+                    let to = entry
+                        .email
+                        .to
+                        .iter()
+                        .map(|a| a.email.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    let subject = if entry.email.subject.is_empty() {
+                        "(no subject)".to_string()
+                    } else {
+                        entry.email.subject.clone()
+                    };
+                    let queued = format_timestamp(entry.queued_at);
+                    let creation_id = entry.creation_id.clone();
+                    let on_delete = move |_| {
+                        let confirmed = web_sys::window()
+                            .and_then(|w| w.confirm_with_message("Discard this message?").ok())
+                            .unwrap_or(false);
+                        if confirmed {
+                            remove_entry(state, &creation_id);
+                        }
+                    };
+                    view! {
+                        <div class="outbox-entry">
+                            <div class="outbox-entry-info">
+                                <div class="outbox-entry-to">{to}</div>
+                                <div class="outbox-entry-subject">{subject}</div>
+                                <div class="outbox-entry-queued">"Queued " {queued}</div>
+                                {entry.last_error.map(|e| view! {
+                                    <div class="error-message">{e}</div>
+                                })}
+                            </div>
+                            <button class="outbox-delete-btn" on:click=on_delete>"Delete"</button>
+                        </div>
+                    }
+                }).collect_view().into_any()
+            }}
+        </div>
+    }
+}
@@ -0,0 +1,163 @@
+use crate::format::format_datetime;
+use crate::outbox::cancel_send;
+use crate::state::AppState;
+use crate::sync::load_submissions;
+use jmap_client::{Delivered, EmailSubmission, UndoStatus};
+use leptos::prelude::*;
+use leptos::task::spawn_local;
+
+/// Recent submissions with their undo status and per-recipient delivery
+/// status. Kept current by `sync_submissions` as push changes arrive.
+#[component]
+pub fn SendingView() -> impl IntoView {
+    let state = use_context::<AppState>().expect("AppState to be provided");
+    let error_msg = RwSignal::new(Option::<String>::None);
+    let load_error = RwSignal::new(Option::<String>::None);
+
+    let load = move || {
+        load_error.set(None);
+        spawn_local(async move {
+            if let Err(e) = load_submissions(state).await {
+                load_error.set(Some(format!("Couldn't load submissions: {e}")));
+            }
+        });
+    };
+
+    // Reload whenever the view opens or we reconnect
+    Effect::new(move || {
+        if state.client.get().is_some() {
+            load();
+        }
+    });
+
+    let on_cancel = move |submission_id: String| {
+        error_msg.set(None);
+        spawn_local(async move {
+            if let Err(e) = cancel_send(state, &submission_id).await {
+                error_msg.set(Some(format!("Couldn't cancel: {e}")));
+            }
+        });
+    };
+
+    view! {
+        <div class="sending">
+            <div class="email-list-toolbar">
+                <span class="sending-title">"Sending"</span>
+            </div>
+            {move || error_msg.get().map(|msg| view! {
+                <div class="error-message sending-error" on:click=move |_| error_msg.set(None)>
+                    {msg}
+                </div>
+            })}
+            {move || {
+                if state.submission_state.get().is_none() {
+                    return match load_error.get() {
+                        Some(msg) => view! {
+                            <div class="error-message sending-error">
+                                {msg}
+                                <button class="sending-retry-btn" on:click=move |_| load()>
+                                    "Retry"
+                                </button>
+                            </div>
+                        }.into_any(),
+                        None => view! { <div class="loading">"Loading..."</div> }.into_any(),
+                    };
+                }
+                let submissions = state.submissions.get();
+                if submissions.is_empty() {
+                    return view! { <div class="empty">"Nothing sent recently"</div> }.into_any();
+                }
+                submissions
+                    .into_iter()
+                    .map(|submission| view! { <SubmissionItem submission on_cancel/> })
+                    .collect_view()
+                    .into_any()
+            }}
+        </div>
+    }
+}
+
+#[component]
+fn SubmissionItem(
+    submission: EmailSubmission,
+    on_cancel: impl Fn(String) + Copy + 'static,
+) -> impl IntoView {
+    let state = use_context::<AppState>().expect("AppState to be provided");
+    let email = state.store.email(&submission.email_id);
+    let subject = email
+        .as_ref()
+        .and_then(|e| e.subject.clone())
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| "(no subject)".to_string());
+    let send_at = submission.send_at.as_deref().map(format_datetime).unwrap_or_default();
+
+    let (status, status_class) = match submission.undo_status {
+        UndoStatus::Pending => (format!("Scheduled for {send_at}"), "pending"),
+        UndoStatus::Final => (format!("Sent {send_at}"), "final"),
+        UndoStatus::Canceled => ("Canceled".to_string(), "canceled"),
+    };
+
+    // Without per-recipient status, fall back to the envelope or headers
+    let mut recipients: Vec<(String, Option<(Delivered, String)>)> =
+        match &submission.delivery_status {
+            Some(statuses) => statuses
+                .iter()
+                .map(|(rcpt, s)| (rcpt.clone(), Some((s.delivered, s.smtp_reply.clone()))))
+                .collect(),
+            None => match (&submission.envelope, &email) {
+                (Some(envelope), _) => {
+                    envelope.rcpt_to.iter().map(|a| (a.email.clone(), None)).collect()
+                }
+                (None, Some(email)) => {
+                    email.to.iter().flatten().map(|a| (a.email.clone(), None)).collect()
+                }
+                (None, None) => vec![],
+            },
+        };
+    recipients.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let can_cancel = submission.undo_status == UndoStatus::Pending;
+    let submission_id = submission.id.clone();
+
+    view! {
+        <div class="submission">
+            <div class="submission-header">
+                <span class="submission-subject">{subject}</span>
+                <span class=format!("submission-status {status_class}")>{status}</span>
+                {can_cancel.then(|| view! {
+                    <button
+                        class="submission-cancel-btn"
+                        on:click=move |_| on_cancel(submission_id.clone())
+                    >"Cancel"</button>
+                })}
+            </div>
+            <ul class="submission-recipients">
+                {recipients.into_iter().map(|(rcpt, status)| {
+                    let (label, class, reply) = match status {
+                        Some((delivered, reply)) => {
+                            let (label, class) = delivered_label(delivered);
+                            (label, class, reply)
+                        }
+                        None => ("", "", String::new()),
+                    };
+                    view! {
+                        <li>
+                            <span class="submission-rcpt">{rcpt}</span>
+                            <span class=format!("delivery-status {class}")>{label}</span>
+                            <span class="smtp-reply">{reply}</span>
+                        </li>
+                    }
+                }).collect_view()}
+            </ul>
+        </div>
+    }
+}
+
+fn delivered_label(delivered: Delivered) -> (&'static str, &'static str) {
+    match delivered {
+        Delivered::Queued => ("Queued", "queued"),
+        Delivered::Yes => ("Delivered", "delivered"),
+        Delivered::No => ("Failed", "failed"),
+        Delivered::Unknown => ("Sent", "unknown"),
+    }
+}
@@ -1,15 +1,27 @@
-use crate::components::compose::ComposeInline;
-use crate::router::mailbox_id_to_slug;
-use crate::state::AppState;
-use jmap_client::Email;
+use crate::components::compose::{
+    matching_identity, parse_addresses, wildcard_domain, ComposeInline,
+};
+use crate::components::email_actions::EmailActions;
+use crate::download::{blob_object_url, revoke_object_url, save_blob};
+use crate::format::format_size;
+use crate::router::{mailbox_id_to_slug, search_query_string, use_search};
+use crate::sanitize::sanitize_html;
+use crate::state::{
+    save_remote_content_allowlist, sender_domain, AppState, RemoteContentAllowlist, ReplyMode,
+};
+use crate::store::set_keyword_optimistic;
+use jmap_client::{Email, EmailAddress, EmailBodyPart, Envelope, SubmissionAddress};
 use leptos::prelude::*;
+use leptos::task::spawn_local;
 use leptos_router::hooks::{use_navigate, use_params_map};
+use std::collections::HashMap;
 
 #[component]
 pub fn ThreadView() -> impl IntoView {
     let state = use_context::<AppState>().expect("AppState to be provided");
     let params = use_params_map();
     let navigate = use_navigate();
+    let search = use_search();
 
     let emails = LocalResource::new(move || {
         let client = state.client.get();
@@ -18,22 +30,52 @@ pub fn ThreadView() -> impl IntoView {
             if tid.is_empty() {
                 return Vec::<Email>::new();
             }
-            let Some(client) = client else {
-                return Vec::<Email>::new();
-            };
-            let thread = match client.get_thread(&tid).await {
-                Ok(t) => t,
-                Err(_) => return vec![],
-            };
-            client
-                .get_email_bodies(&thread.email_ids)
-                .await
-                .ok()
-                .unwrap_or_default()
+            // Cached threads are readable offline
+            match state.store.thread_with_bodies(&tid) {
+                Some(emails) => emails,
+                None => {
+                    let Some(client) = client else {
+                        return vec![];
+                    };
+                    let mut batch = client.batch();
+                    let thread_call = batch.thread_get(vec![tid]);
+                    let email_call =
+                        batch.email_get_bodies(thread_call.reference("/list/*/emailIds"));
+                    let Ok(response) = batch.send().await else {
+                        return vec![];
+                    };
+                    if let Ok(threads) = response.get(&thread_call) {
+                        state.store.merge_threads(&threads.list, &threads.state);
+                    }
+                    let Ok(got) = response.get(&email_call) else {
+                        return vec![];
+                    };
+                    state.store.merge_emails(&got.list, &got.state);
+                    got.list
+                }
+            }
         }
     });
 
-    let on_back = move |_| {
+    // Opening a thread marks it read, once per thread. Kept out of the
+    // resource so keyword changes don't reload the thread.
+    let marked_read = StoredValue::new(String::new());
+    Effect::new(move || {
+        let Some(email_list) = emails.get() else { return };
+        let tid = params.with_untracked(|p| p.get("thread_id").unwrap_or_default().to_string());
+        if email_list.is_empty() || marked_read.with_value(|marked| *marked == tid) {
+            return;
+        }
+        marked_read.set_value(tid);
+        let unread: Vec<String> = email_list
+            .iter()
+            .filter_map(|e| e.id.clone())
+            .filter(|id| !state.store.email(id).is_some_and(|e| e.has_keyword("$seen")))
+            .collect();
+        set_keyword_optimistic(state, unread, "$seen", true);
+    });
+
+    let go_back = move || {
         let mailboxes = state.mailboxes.get();
         let slug = state
             .selected_mailbox
@@ -42,13 +84,25 @@ pub fn ThreadView() -> impl IntoView {
             .map(|id| mailbox_id_to_slug(&mailboxes, id))
             .unwrap_or_else(|| "inbox".to_string());
         state.reply_to_email.set(None);
-        navigate(&format!("/mail/{slug}"), Default::default());
+        // Return to the search results if we came from a search
+        let (query, all_mailboxes) = search.get_untracked();
+        let suffix = search_query_string(&query, all_mailboxes);
+        navigate(&format!("/mail/{slug}{suffix}"), Default::default());
     };
+    let on_back = {
+        let go_back = go_back.clone();
+        move |_| go_back()
+    };
+
+    let thread_ids = Signal::derive(move || {
+        params.with(|p| p.get("thread_id").into_iter().collect::<Vec<_>>())
+    });
 
     view! {
         <div class="thread-view">
             <div class="thread-toolbar">
                 <button on:click=on_back>"Back"</button>
+                <EmailActions thread_ids=thread_ids on_done=go_back/>
             </div>
             {move || {
                 match emails.get() {
@@ -79,8 +133,14 @@ pub fn ThreadView() -> impl IntoView {
 fn EmailCard(email: Email) -> impl IntoView {
     let state = use_context::<AppState>().expect("AppState to be provided");
     let email_id = email.id.clone().unwrap_or_default();
-    let email_id_reply = email_id.clone();
-    let email_id_reply_all = email_id.clone();
+    let email_id_redirect = email_id.clone();
+    let respond = {
+        let email_id = email_id.clone();
+        move |mode: ReplyMode| {
+            state.reply_to_email.set(Some(email_id.clone()));
+            state.reply_mode.set(mode);
+        }
+    };
 
     let from = email
         .from
@@ -123,15 +183,73 @@ fn EmailCard(email: Email) -> impl IntoView {
         })
         .unwrap_or_else(|| "(no text content)".to_string());
 
-    let on_reply = move |_| {
-        state.reply_to_email.set(Some(email_id_reply.clone()));
-        state.reply_all.set(false);
+    // Only treat the email as HTML if htmlBody actually contains text/html;
+    // for plain-text-only messages the server repeats the text parts there.
+    let html = email.html_body.as_ref().and_then(|parts| {
+        let html: String = parts
+            .iter()
+            .filter(|part| part.type_.as_deref() == Some("text/html"))
+            .filter_map(|part| {
+                let part_id = part.part_id.as_ref()?;
+                email.body_values.as_ref()?.get(part_id).map(|v| v.value.as_str())
+            })
+            .collect();
+        (!html.is_empty()).then_some(html)
+    });
+    let has_html = html.is_some();
+    let show_html = RwSignal::new(has_html);
+
+    // Parts referenced from the HTML by Content-ID (inline images)
+    let inline_parts: Vec<EmailBodyPart> = email
+        .html_body
+        .iter()
+        .chain(email.attachments.iter())
+        .flatten()
+        .filter(|part| part.cid.is_some() && part.blob_id.is_some())
+        .cloned()
+        .collect();
+
+    let sender = email
+        .from
+        .as_ref()
+        .and_then(|addrs| addrs.first())
+        .map(|a| a.email.clone());
+
+    let attachments = email.attachments.clone().unwrap_or_default();
+
+    // Keywords come from the store so changes made elsewhere show up here
+    let is_seen = {
+        let email_id = email_id.clone();
+        Memo::new(move |_| state.store.has_keyword(&email_id, "$seen"))
+    };
+    let is_flagged = {
+        let email_id = email_id.clone();
+        Memo::new(move |_| state.store.has_keyword(&email_id, "$flagged"))
     };
 
-    let on_reply_all = move |_| {
-        state.reply_to_email.set(Some(email_id_reply_all.clone()));
-        state.reply_all.set(true);
+    let toggle = move |signal: Memo<bool>, keyword: &'static str| {
+        set_keyword_optimistic(state, vec![email_id.clone()], keyword, !signal.get_untracked());
     };
+    let toggle_seen = toggle.clone();
+    let on_toggle_seen = move |_| toggle_seen(is_seen, "$seen");
+    let on_toggle_flag = move |_| toggle(is_flagged, "$flagged");
+
+    let on_reply = {
+        let respond = respond.clone();
+        move |_| respond(ReplyMode::Reply)
+    };
+    let on_reply_all = {
+        let respond = respond.clone();
+        move |_| respond(ReplyMode::ReplyAll)
+    };
+    let on_forward = {
+        let respond = respond.clone();
+        move |_| respond(ReplyMode::Forward)
+    };
+    let on_forward_attachment = move |_| respond(ReplyMode::ForwardAsAttachment);
+
+    let redirecting = RwSignal::new(false);
+    let redirect_notice = RwSignal::new(Option::<String>::None);
 
     view! {
         <div class="email-card">
@@ -140,14 +258,312 @@ fn EmailCard(email: Email) -> impl IntoView {
                 <div class="email-card-to"><strong>"To: "</strong>{to}</div>
                 <div class="email-card-date"><strong>"Date: "</strong>{date}</div>
                 <div class="email-card-subject"><strong>"Subject: "</strong>{subject}</div>
+                {has_html.then(|| view! {
+                    <div class="body-format-toggle">
+                        <button
+                            class:active=move || show_html.get()
+                            on:click=move |_| show_html.set(true)
+                        >"HTML"</button>
+                        <button
+                            class:active=move || !show_html.get()
+                            on:click=move |_| show_html.set(false)
+                        >"Plain text"</button>
+                    </div>
+                })}
             </div>
             <div class="email-card-body">
-                <pre>{body_text}</pre>
+                {move || match (show_html.get(), html.clone()) {
+                    (true, Some(html)) => view! {
+                        <HtmlBody
+                            html=html
+                            inline_parts=inline_parts.clone()
+                            sender=sender.clone()
+                        />
+                    }.into_any(),
+                    _ => view! { <pre>{body_text.clone()}</pre> }.into_any(),
+                }}
             </div>
+            {(!attachments.is_empty()).then(|| view! {
+                <div class="email-card-attachments">
+                    {attachments.into_iter().map(|part| view! { <AttachmentItem part=part/> }).collect_view()}
+                </div>
+            })}
             <div class="email-card-actions">
                 <button on:click=on_reply>"Reply"</button>
                 <button on:click=on_reply_all>"Reply All"</button>
+                <button on:click=on_forward>"Forward"</button>
+                <button on:click=on_forward_attachment>"Forward as attachment"</button>
+                <button on:click=move |_| redirecting.update(|r| *r = !*r)>"Redirect"</button>
+                <div class="toolbar-spacer"></div>
+                <button on:click=on_toggle_seen>
+                    {move || if is_seen.get() { "Mark unread" } else { "Mark read" }}
+                </button>
+                <button class:flagged=move || is_flagged.get() on:click=on_toggle_flag>
+                    {move || if is_flagged.get() { "Unflag" } else { "Flag" }}
+                </button>
             </div>
+            {move || redirecting.get().then(|| view! {
+                <RedirectForm
+                    email_id=email_id_redirect.clone()
+                    on_done=move |notice| {
+                        redirecting.set(false);
+                        redirect_notice.set(Some(notice));
+                    }
+                />
+            })}
+            {move || redirect_notice.get().map(|notice| view! {
+                <div class="redirect-notice">{notice}</div>
+            })}
+        </div>
+    }
+}
+
+/// Pass a message on unchanged to new recipients. The existing email is
+/// submitted with an explicit envelope, so its From and other headers are
+/// kept and no new email is created.
+#[component]
+fn RedirectForm(email_id: String, on_done: impl Fn(String) + Clone + 'static) -> impl IntoView {
+    let state = use_context::<AppState>().expect("AppState to be provided");
+    let recipients = RwSignal::new(String::new());
+    let busy = RwSignal::new(false);
+    let error_msg = RwSignal::new(Option::<String>::None);
+
+    let on_submit = move |ev: leptos::ev::SubmitEvent| {
+        ev.prevent_default();
+        let rcpt_to = parse_addresses(&recipients.get_untracked());
+        if rcpt_to.is_empty() || busy.get_untracked() {
+            return;
+        }
+        let Some(client) = state.client.get_untracked() else {
+            error_msg.set(Some("Not connected".to_string()));
+            return;
+        };
+        // Redirect as whichever identity the original was sent to, like a
+        // reply would, falling back to the first one with a real address
+        let original = state.store.email(&email_id);
+        let sender = state.identities.with_untracked(|identities| {
+            let recipients: Vec<&EmailAddress> = original
+                .iter()
+                .flat_map(|e| e.to.iter().chain(&e.cc).chain(&e.bcc).flatten())
+                .collect();
+            matching_identity(identities, &recipients)
+                .or_else(|| {
+                    let identity = identities.iter().find(|i| wildcard_domain(i).is_none())?;
+                    Some((identity, identity.email.clone()))
+                })
+                .map(|(identity, address)| (identity.id.clone(), address))
+        });
+        let Some((identity_id, mail_from)) = sender else {
+            error_msg.set(Some("No identity found".to_string()));
+            return;
+        };
+        let envelope = Envelope {
+            mail_from: SubmissionAddress::new(&mail_from),
+            rcpt_to: rcpt_to.iter().map(|a| SubmissionAddress::new(&a.email)).collect(),
+        };
+        let email_id = email_id.clone();
+        let on_done = on_done.clone();
+
+        busy.set(true);
+        error_msg.set(None);
+        spawn_local(async move {
+            match client.submit_email(&identity_id, &email_id, Some(&envelope)).await {
+                Ok(_) => {
+                    let to = rcpt_to.iter().map(|a| a.email.as_str()).collect::<Vec<_>>();
+                    on_done(format!("Redirected to {}", to.join(", ")));
+                }
+                Err(e) => {
+                    error_msg.set(Some(format!("Redirect failed: {e}")));
+                    busy.set(false);
+                }
+            }
+        });
+    };
+
+    view! {
+        <form class="redirect-form" on:submit=on_submit>
+            <input
+                type="text"
+                placeholder="Redirect to recipient@example.com"
+                bind:value=recipients
+            />
+            <button type="submit" disabled=move || busy.get()>
+                {move || if busy.get() { "Redirecting..." } else { "Redirect" }}
+            </button>
+            {move || error_msg.get().map(|msg| view! {
+                <div class="error-message">{msg}</div>
+            })}
+        </form>
+    }
+}
+
+/// Render an HTML body in a sandboxed iframe after sanitizing it and
+/// resolving `cid:` references to object URLs for the inline parts.
+/// Remote content is blocked unless the sender is on the allowlist or the
+/// user opts in for this message.
+#[component]
+fn HtmlBody(
+    html: String,
+    inline_parts: Vec<EmailBodyPart>,
+    sender: Option<String>,
+) -> impl IntoView {
+    let state = use_context::<AppState>().expect("AppState to be provided");
+    let object_urls = StoredValue::new(Vec::<String>::new());
+    let iframe_ref = NodeRef::<leptos::html::Iframe>::new();
+    let allow_once = RwSignal::new(false);
+
+    let cid_urls = LocalResource::new(move || {
+        let client = state.client.get();
+        let inline_parts = inline_parts.clone();
+        async move {
+            let mut cid_urls = HashMap::new();
+            if let Some(client) = client {
+                for part in inline_parts {
+                    let (Some(cid), Some(blob_id)) = (part.cid, part.blob_id) else {
+                        continue;
+                    };
+                    let type_ = part
+                        .type_
+                        .unwrap_or_else(|| "application/octet-stream".to_string());
+                    let name = part.name.unwrap_or_else(|| "inline".to_string());
+                    if let Ok(url) = blob_object_url(&client, &blob_id, &type_, &name).await {
+                        object_urls.update_value(|urls| urls.push(url.clone()));
+                        cid_urls.insert(cid, url);
+                    }
+                }
+            }
+            cid_urls
+        }
+    });
+
+    on_cleanup(move || {
+        object_urls.with_value(|urls| urls.iter().for_each(|url| revoke_object_url(url)));
+    });
+
+    let sender_allowed = {
+        let sender = sender.clone();
+        move || {
+            sender.as_deref().is_some_and(|s| {
+                state
+                    .remote_content_allowlist
+                    .with(|allowlist| allowlist.allows(s))
+            })
+        }
+    };
+    let allow_remote = move || allow_once.get() || sender_allowed();
+
+    let sanitized = move || {
+        let cid_urls = cid_urls.get()?;
+        Some(sanitize_html(&html, &cid_urls, allow_remote()).ok())
+    };
+
+    // Size the iframe to its content so the card scrolls with the thread
+    let on_load = move |_| {
+        let Some(iframe) = iframe_ref.get() else { return };
+        let height = iframe
+            .content_document()
+            .and_then(|doc| doc.document_element())
+            .map(|el| el.scroll_height())
+            .unwrap_or(0);
+        let _ = iframe.set_attribute("style", &format!("height: {height}px;"));
+    };
+
+    let allow_permanently = move |update: &dyn Fn(&mut RemoteContentAllowlist)| {
+        state.remote_content_allowlist.update(|allowlist| {
+            update(allowlist);
+            save_remote_content_allowlist(allowlist);
+        });
+    };
+
+    let banner = move || {
+        let sender_buttons = sender.clone().map(|sender| {
+            let domain = sender_domain(&sender).map(|d| d.to_string());
+            let on_allow_sender = {
+                let sender = sender.clone();
+                move |_| allow_permanently(&|allowlist| allowlist.allow_sender(&sender))
+            };
+            view! {
+                <button on:click=on_allow_sender>{format!("Always load from {sender}")}</button>
+                {domain.map(|domain| {
+                    let label = format!("Always load from {domain}");
+                    let on_allow_domain =
+                        move |_| allow_permanently(&|allowlist| allowlist.allow_domain(&domain));
+                    view! { <button on:click=on_allow_domain>{label}</button> }
+                })}
+            }
+        });
+        view! {
+            <div class="remote-content-banner">
+                <span>"Remote content has been blocked to protect your privacy."</span>
+                <button on:click=move |_| allow_once.set(true)>"Load remote content"</button>
+                {sender_buttons}
+            </div>
+        }
+    };
+
+    view! {
+        {move || match sanitized() {
+            None => view! { <div class="loading">"Loading..."</div> }.into_any(),
+            Some(None) => view! { <div class="error-message">"Could not render HTML"</div> }.into_any(),
+            Some(Some(doc)) => view! {
+                {doc.blocked_remote.then(banner.clone())}
+                // No allow-scripts: nothing in the email can execute.
+                // allow-same-origin only lets us measure the content height.
+                <iframe
+                    class="email-html-body"
+                    node_ref=iframe_ref
+                    sandbox="allow-same-origin allow-popups allow-popups-to-escape-sandbox"
+                    srcdoc=doc.html
+                    on:load=on_load
+                ></iframe>
+            }.into_any(),
+        }}
+    }
+}
+
+#[component]
+fn AttachmentItem(part: EmailBodyPart) -> impl IntoView {
+    let state = use_context::<AppState>().expect("AppState to be provided");
+    let downloading = RwSignal::new(false);
+    let error_msg = RwSignal::new(Option::<String>::None);
+
+    let name = part.name.clone().unwrap_or_else(|| "attachment".to_string());
+    let type_ = part
+        .type_
+        .clone()
+        .unwrap_or_else(|| "application/octet-stream".to_string());
+    let size = part.size.map(format_size).unwrap_or_default();
+
+    let on_download = {
+        let name = name.clone();
+        let type_ = type_.clone();
+        move |ev: leptos::ev::MouseEvent| {
+            ev.prevent_default();
+            let Some(blob_id) = part.blob_id.clone() else { return };
+            let Some(client) = state.client.get_untracked() else { return };
+            if downloading.get_untracked() {
+                return;
+            }
+            let name = name.clone();
+            let type_ = type_.clone();
+            downloading.set(true);
+            error_msg.set(None);
+            spawn_local(async move {
+                if let Err(e) = save_blob(&client, &blob_id, &type_, &name).await {
+                    error_msg.set(Some(format!("Download failed: {e}")));
+                }
+                downloading.set(false);
+            });
+        }
+    };
+
+    view! {
+        <div class="attachment-item">
+            <a href="#" class="attachment-name" on:click=on_download>{name}</a>
+            <span class="attachment-size">{size}</span>
+            <span class="attachment-type">{type_}</span>
+            {move || downloading.get().then(|| view! { <span class="attachment-status">"Downloading..."</span> })}
+            {move || error_msg.get().map(|msg| view! { <span class="attachment-error">{msg}</span> })}
         </div>
     }
 }
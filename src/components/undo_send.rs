@@ -0,0 +1,75 @@
+use crate::outbox::cancel_send;
+use crate::router::mailbox_id_to_slug;
+use crate::state::AppState;
+use leptos::prelude::*;
+use leptos::task::spawn_local;
+use leptos_router::hooks::use_navigate;
+use std::time::Duration;
+
+/// "Message sent" notice with an Undo button, shown while the last send is
+/// still held. Undoing cancels the submission and reopens the message from
+/// Drafts.
+#[component]
+pub fn UndoSendBar() -> impl IntoView {
+    let state = use_context::<AppState>().expect("AppState to be provided");
+    let navigate = use_navigate();
+    let busy = RwSignal::new(false);
+    let error_msg = RwSignal::new(Option::<String>::None);
+
+    // Hide the bar once the hold ends
+    Effect::new(move || {
+        let Some(pending) = state.pending_undo.get() else { return };
+        error_msg.set(None);
+        let remaining = (pending.until - js_sys::Date::now()).max(0.0);
+        set_timeout(
+            move || {
+                let current = state.pending_undo.get_untracked();
+                if current.is_some_and(|c| c.submission_id == pending.submission_id) {
+                    state.pending_undo.set(None);
+                }
+            },
+            Duration::from_millis(remaining as u64),
+        );
+    });
+
+    let on_undo = move |_| {
+        let Some(pending) = state.pending_undo.get_untracked() else { return };
+        let navigate = navigate.clone();
+        busy.set(true);
+        spawn_local(async move {
+            let result = cancel_send(state, &pending.submission_id).await;
+            busy.set(false);
+            state.pending_undo.set(None);
+            match result {
+                Ok(()) => {
+                    let mailboxes = state.mailboxes.get_untracked();
+                    let drafts = mailboxes
+                        .iter()
+                        .find(|m| m.role.as_deref() == Some("drafts"))
+                        .map(|m| mailbox_id_to_slug(&mailboxes, &m.id))
+                        .unwrap_or_else(|| "drafts".to_string());
+                    let draft = String::from(js_sys::encode_uri_component(&pending.email_id));
+                    navigate(
+                        &format!("/mail/{drafts}/compose?draft={draft}"),
+                        Default::default(),
+                    );
+                }
+                Err(e) => error_msg.set(Some(format!("Couldn't undo: {e}"))),
+            }
+        });
+    };
+
+    view! {
+        {move || state.pending_undo.get().map(|_| view! {
+            <div class="undo-send-bar">
+                <span>"Message sent."</span>
+                <button disabled=move || busy.get() on:click=on_undo.clone()>"Undo"</button>
+            </div>
+        })}
+        {move || error_msg.get().map(|msg| view! {
+            <div class="error-message undo-send-error" on:click=move |_| error_msg.set(None)>
+                {msg}
+            </div>
+        })}
+    }
+}
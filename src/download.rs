@@ -0,0 +1,63 @@
+use jmap_client::JmapClient;
+use wasm_bindgen::prelude::*;
+
+/// Fetch a blob with the client's credentials and wrap it in an object URL.
+/// The download URL can't be used directly in the DOM because Basic auth
+/// headers can't be attached to a plain anchor or image request.
+/// Callers should revoke the URL with `revoke_object_url` when done.
+pub async fn blob_object_url(
+    client: &JmapClient,
+    blob_id: &str,
+    content_type: &str,
+    name: &str,
+) -> Result<String, String> {
+    let bytes = client
+        .download_blob(blob_id, content_type, name)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let array = js_sys::Uint8Array::from(bytes.as_slice());
+    let parts = js_sys::Array::of1(&array);
+    let options = web_sys::BlobPropertyBag::new();
+    options.set_type(content_type);
+    let blob = web_sys::Blob::new_with_u8_array_sequence_and_options(&parts, &options)
+        .map_err(js_error)?;
+    web_sys::Url::create_object_url_with_blob(&blob).map_err(js_error)
+}
+
+pub fn revoke_object_url(url: &str) {
+    let _ = web_sys::Url::revoke_object_url(url);
+}
+
+/// Download a blob and hand it to the browser as a file save.
+pub async fn save_blob(
+    client: &JmapClient,
+    blob_id: &str,
+    content_type: &str,
+    name: &str,
+) -> Result<(), String> {
+    let url = blob_object_url(client, blob_id, content_type, name).await?;
+
+    let document = web_sys::window()
+        .and_then(|w| w.document())
+        .ok_or_else(|| "No document".to_string())?;
+    let anchor: web_sys::HtmlAnchorElement = document
+        .create_element("a")
+        .map_err(js_error)?
+        .unchecked_into();
+    anchor.set_href(&url);
+    anchor.set_download(name);
+    anchor.click();
+
+    // Revoking synchronously can cancel the download in some browsers
+    let revoke = Closure::once_into_js(move || revoke_object_url(&url));
+    if let Some(window) = web_sys::window() {
+        let _ = window
+            .set_timeout_with_callback_and_timeout_and_arguments_0(revoke.unchecked_ref(), 1000);
+    }
+    Ok(())
+}
+
+fn js_error(e: JsValue) -> String {
+    e.as_string().unwrap_or_else(|| format!("{e:?}"))
+}
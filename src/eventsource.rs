@@ -175,12 +175,11 @@ async fn open_sse_stream(
             let line = leftover[..newline_pos].trim_end_matches('\r').to_string();
             leftover = leftover[newline_pos + 1..].to_string();
 
-            if let Some((event_type, data)) = parser.feed_line(&line) {
-                if event_type == "state" {
-                    if let Ok(change) = serde_json::from_str::<StateChange>(&data) {
-                        handle_state_change(state, change);
-                    }
-                }
+            if let Some((event_type, data)) = parser.feed_line(&line)
+                && event_type == "state"
+                && let Ok(change) = serde_json::from_str::<StateChange>(&data)
+            {
+                handle_state_change(state, change);
             }
         }
 
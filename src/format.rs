@@ -0,0 +1,31 @@
+/// Format a byte count for display (e.g. "512 B", "1.2 KB", "3.4 MB").
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["KB", "MB", "GB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+/// Format a UTC date-time such as "2024-01-01T09:00:00Z" in the user's
+/// locale and time zone, or return it unchanged if it can't be parsed.
+pub fn format_datetime(date: &str) -> String {
+    let ms = js_sys::Date::parse(date);
+    if ms.is_nan() {
+        return date.to_string();
+    }
+    format_timestamp(ms)
+}
+
+/// Format milliseconds since the epoch in the user's locale and time zone.
+pub fn format_timestamp(ms: f64) -> String {
+    js_sys::Date::new(&ms.into())
+        .to_locale_string("default", &js_sys::Object::new())
+        .into()
+}
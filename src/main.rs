@@ -1,10 +1,18 @@
 mod app;
+mod cache;
 mod components;
+mod download;
 mod eventsource;
+mod format;
+mod outbox;
 mod pages;
 mod router;
+mod sanitize;
+mod search;
+mod store;
 mod state;
 mod sync;
+mod upload;
 
 fn main() {
     console_error_panic_hook::set_once();
@@ -0,0 +1,228 @@
+use crate::state::AppState;
+use jmap_client::{JmapClient, JmapError, OutgoingEmail, Release, SentEmail, UndoStatus};
+use leptos::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+
+/// A message waiting to be sent while the server is unreachable.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct OutboxEntry {
+    /// Client-generated ID, also sent as the Message-ID so a retry can
+    /// tell whether an earlier attempt reached the server.
+    pub creation_id: String,
+    pub identity_id: String,
+    pub email: OutgoingEmail,
+    /// Milliseconds since the epoch.
+    pub queued_at: f64,
+    #[serde(default)]
+    pub last_error: Option<String>,
+}
+
+pub enum SendOutcome {
+    Sent(SentEmail),
+    Queued,
+}
+
+/// A just-sent message held for the undo window.
+#[derive(Clone)]
+pub struct UndoableSend {
+    pub email_id: String,
+    pub submission_id: String,
+    /// When the hold ends, in milliseconds since the epoch.
+    pub until: f64,
+}
+
+/// Emails forwarded by a held submission. They're marked `$forwarded` once
+/// it's released, and left alone if it's canceled.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HeldForward {
+    pub submission_id: String,
+    pub forwarded: Vec<String>,
+}
+
+thread_local! {
+    static FLUSHING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Send an email, or queue it in the outbox if there's no connection.
+/// Errors other than network failures are returned as-is.
+pub async fn send_or_queue(
+    state: AppState,
+    identity_id: String,
+    mut email: OutgoingEmail,
+) -> Result<SendOutcome, JmapError> {
+    let sender = email.from.first().map(|a| a.email.clone()).unwrap_or_default();
+    let creation_id = email
+        .message_id
+        .get_or_insert_with(|| new_message_id(&sender))
+        .clone();
+
+    if let Some(client) = state.client.get_untracked() {
+        match submit(state, &client, &identity_id, &email).await {
+            Ok(sent) => return Ok(SendOutcome::Sent(sent)),
+            Err(e) if e.is_network() => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    // There's no undoing a queued message, so it goes as soon as it can
+    if matches!(email.release, Some(Release::After(_))) {
+        email.release = None;
+    }
+
+    state.outbox.update(|outbox| {
+        outbox.push(OutboxEntry {
+            creation_id,
+            identity_id,
+            email,
+            queued_at: js_sys::Date::now(),
+            last_error: None,
+        })
+    });
+    Ok(SendOutcome::Queued)
+}
+
+/// Try to send everything in the outbox, stopping at the first network
+/// failure. Messages rejected by the server stay queued with their error,
+/// and the rest are still tried.
+pub async fn flush_outbox(state: AppState) {
+    if FLUSHING.replace(true) {
+        return;
+    }
+    for entry in state.outbox.get_untracked() {
+        let Some(client) = state.client.get_untracked() else { break };
+        match replay(state, &client, &entry).await {
+            Ok(()) => remove_entry(state, &entry.creation_id),
+            Err(e) if e.is_network() => break,
+            Err(e) => state.outbox.update(|outbox| {
+                let queued = outbox.iter_mut().find(|q| q.creation_id == entry.creation_id);
+                if let Some(queued) = queued {
+                    queued.last_error = Some(e.to_string());
+                }
+            }),
+        }
+    }
+    FLUSHING.set(false);
+}
+
+pub fn remove_entry(state: AppState, creation_id: &str) {
+    state
+        .outbox
+        .update(|outbox| outbox.retain(|e| e.creation_id != creation_id));
+}
+
+/// Send a queued message unless an earlier attempt already did. A copy
+/// still marked `$draft` was created but never submitted, so it's replaced.
+/// A scheduled time that passed while offline sends it immediately.
+async fn replay(
+    state: AppState,
+    client: &JmapClient,
+    entry: &OutboxEntry,
+) -> Result<(), JmapError> {
+    if let Some(existing) = client.find_email_by_message_id(&entry.creation_id).await? {
+        if !existing.has_keyword("$draft") {
+            return Ok(());
+        }
+        if let Some(id) = existing.id {
+            client.destroy_emails(&[id]).await?;
+        }
+    }
+    let mut email = entry.email.clone();
+    if let Some(Release::At(date)) = &email.release
+        && js_sys::Date::parse(date) <= js_sys::Date::now()
+    {
+        email.release = None;
+    }
+    submit(state, client, &entry.identity_id, &email).await.map(|_| ())
+}
+
+async fn submit(
+    state: AppState,
+    client: &JmapClient,
+    identity_id: &str,
+    email: &OutgoingEmail,
+) -> Result<SentEmail, JmapError> {
+    let mailboxes = state.mailboxes.get_untracked();
+    let drafts_id = client
+        .find_mailbox_by_role(&mailboxes, "drafts")
+        .map(|m| m.id.clone())
+        .unwrap_or_default();
+    let sent_id = client
+        .find_mailbox_by_role(&mailboxes, "sent")
+        .map(|m| m.id.clone())
+        .unwrap_or_default();
+    let sent = client.send_email(identity_id, email, &drafts_id, &sent_id).await?;
+    if email.release.is_some() && !email.forwarded.is_empty() {
+        state.held_forwards.update(|held| {
+            held.push(HeldForward {
+                submission_id: sent.submission_id.clone(),
+                forwarded: email.forwarded.clone(),
+            })
+        });
+    }
+    Ok(sent)
+}
+
+/// Mark the originals of held forwards `$forwarded` once their submission
+/// is final. Canceled (or vanished) submissions are dropped unmarked.
+pub async fn mark_released_forwards(state: AppState) {
+    let held = state.held_forwards.get_untracked();
+    let Some(client) = state.client.get_untracked() else { return };
+    if held.is_empty() {
+        return;
+    }
+    let ids: Vec<String> = held.iter().map(|h| h.submission_id.clone()).collect();
+    let mut batch = client.batch();
+    let call = batch.email_submission_get(ids);
+    let Ok(submissions) = batch.send().await.and_then(|r| r.get(&call)) else { return };
+
+    let mut settled = vec![];
+    for entry in held {
+        let submission = submissions.list.iter().find(|s| s.id == entry.submission_id);
+        match submission.map(|s| s.undo_status) {
+            Some(UndoStatus::Pending) => continue,
+            Some(UndoStatus::Final) => {
+                if client.set_keyword(&entry.forwarded, "$forwarded", true).await.is_err() {
+                    continue;
+                }
+            }
+            Some(UndoStatus::Canceled) | None => {}
+        }
+        settled.push(entry.submission_id);
+    }
+    state
+        .held_forwards
+        .update(|held| held.retain(|h| !settled.contains(&h.submission_id)));
+}
+
+/// Cancel a held submission, returning its email to Drafts.
+pub async fn cancel_send(state: AppState, submission_id: &str) -> Result<(), JmapError> {
+    let client = state
+        .client
+        .get_untracked()
+        .ok_or_else(|| JmapError::Api("Not connected".to_string()))?;
+    let mailboxes = state.mailboxes.get_untracked();
+    let drafts_id = client
+        .find_mailbox_by_role(&mailboxes, "drafts")
+        .map(|m| m.id.clone())
+        .unwrap_or_default();
+    let sent_id = client
+        .find_mailbox_by_role(&mailboxes, "sent")
+        .map(|m| m.id.clone())
+        .unwrap_or_default();
+    client
+        .cancel_submission(submission_id, &drafts_id, &sent_id)
+        .await
+}
+
+/// A unique Message-ID (without angle brackets) in the sender's domain.
+fn new_message_id(sender: &str) -> String {
+    let random = || (js_sys::Math::random() * f64::from(u32::MAX)) as u32;
+    let domain = crate::state::sender_domain(sender).unwrap_or("jmap-webmail.invalid");
+    format!(
+        "{:x}.{:08x}{:08x}@{domain}",
+        js_sys::Date::now() as u64,
+        random(),
+        random()
+    )
+}
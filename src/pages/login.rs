@@ -30,11 +30,13 @@ pub fn LoginPage() -> impl IntoView {
                 Ok(client) => {
                     let (mailboxes, mailbox_state) =
                         client.get_mailboxes().await.ok().unwrap_or_default();
-                    let identities = client.get_identities().await.ok().unwrap_or_default();
+                    let (identities, identity_state) =
+                        client.get_identities().await.ok().unwrap_or_default();
 
                     state.mailboxes.set(mailboxes);
                     state.mailbox_state.set(Some(mailbox_state));
                     state.identities.set(identities);
+                    state.identity_state.set(Some(identity_state));
                     state.client.set(Some(client));
 
                     save_credentials(&server, &user, &pass);
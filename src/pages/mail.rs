@@ -1,6 +1,7 @@
 use crate::components::mailbox_sidebar::MailboxSidebar;
-use crate::router::{mailbox_id_to_slug, slug_to_mailbox_id};
-use crate::state::AppState;
+use crate::components::undo_send::UndoSendBar;
+use crate::router::{mailbox_id_to_slug, search_query_string, slug_to_mailbox_id, use_search};
+use crate::state::{AppState, ReplyMode};
 use leptos::prelude::*;
 use leptos_router::components::{Outlet, Redirect};
 use leptos_router::hooks::{use_navigate, use_params_map};
@@ -10,28 +11,40 @@ pub fn MailLayout() -> impl IntoView {
     let state = use_context::<AppState>().expect("AppState to be provided");
     let params = use_params_map();
     let navigate = use_navigate();
+    let search = use_search();
+    let search_input = RwSignal::new(String::new());
+    let search_all = RwSignal::new(false);
 
     // Sync mailbox from URL param → signal
     Effect::new(move || {
         let p = params.read();
         let slug = p.get("mailbox").unwrap_or_default();
         let mailboxes = state.mailboxes.get();
-        if let Some(id) = slug_to_mailbox_id(&mailboxes, &slug) {
-            if state.selected_mailbox.get_untracked().as_deref() != Some(id.as_str()) {
-                state.selected_mailbox.set(Some(id));
-            }
+        if let Some(id) = slug_to_mailbox_id(&mailboxes, &slug)
+            && state.selected_mailbox.get_untracked().as_deref() != Some(id.as_str())
+        {
+            state.selected_mailbox.set(Some(id));
         }
     });
 
+    // Keep the search box in sync with the URL (e.g. when switching mailbox)
+    Effect::new(move || {
+        let (query, all) = search.get();
+        search_input.set(query);
+        search_all.set(all);
+    });
+
     view! {
         {move || {
             let client = state.client.get();
             let auto_done = state.auto_login_done.get();
+            // Cached mail is shown while connecting or offline
+            let offline = state.offline.get();
 
-            if client.is_none() && !auto_done {
+            if client.is_none() && !offline && !auto_done {
                 return view! { <div class="loading">"Connecting..."</div> }.into_any();
             }
-            if client.is_none() {
+            if client.is_none() && !offline {
                 return view! { <Redirect path="/login"/> }.into_any();
             }
 
@@ -47,8 +60,39 @@ pub fn MailLayout() -> impl IntoView {
                 nav(&format!("/mail/{slug}/compose"), Default::default());
             };
 
+            let nav = navigate.clone();
+            let on_search = move |ev: leptos::ev::SubmitEvent| {
+                ev.prevent_default();
+                let mailboxes = state.mailboxes.get();
+                let slug = state
+                    .selected_mailbox
+                    .get()
+                    .as_deref()
+                    .map(|id| mailbox_id_to_slug(&mailboxes, id))
+                    .unwrap_or_else(|| "inbox".to_string());
+                let suffix = search_query_string(&search_input.get(), search_all.get());
+                nav(&format!("/mail/{slug}{suffix}"), Default::default());
+            };
+
+            let nav = navigate.clone();
+            let on_settings = move |_| {
+                state.reply_to_email.set(None);
+                state.reply_mode.set(ReplyMode::Reply);
+                nav("/settings", Default::default());
+            };
+
             let nav = navigate.clone();
             let on_logout = move |_| {
+                let queued = state.outbox.with_untracked(|outbox| outbox.len());
+                if queued > 0 {
+                    let message = format!("{queued} unsent messages will be discarded. Log out?");
+                    let confirmed = web_sys::window()
+                        .and_then(|w| w.confirm_with_message(&message).ok())
+                        .unwrap_or(false);
+                    if !confirmed {
+                        return;
+                    }
+                }
                 state.logout();
                 nav("/login", Default::default());
             };
@@ -57,7 +101,26 @@ pub fn MailLayout() -> impl IntoView {
                 <div class="mail-layout">
                     <div class="mail-toolbar">
                         <button class="compose-btn" on:click=on_compose>"Compose"</button>
+                        <form class="search-form" on:submit=on_search>
+                            <input
+                                type="search"
+                                placeholder="Search (from:, to:, subject:, has:attachment, is:unread, before:, after:)"
+                                bind:value=search_input
+                            />
+                            <label class="search-scope">
+                                <input type="checkbox" bind:checked=search_all/>
+                                "All folders"
+                            </label>
+                        </form>
                         <div class="toolbar-spacer"></div>
+                        {move || state.offline.get().then(|| view! {
+                            <span class="offline-indicator" title="Showing cached mail">
+                                {move || {
+                                    if state.auto_login_done.get() { "Offline" } else { "Connecting..." }
+                                }}
+                            </span>
+                        })}
+                        <button class="settings-btn" on:click=on_settings>"Settings"</button>
                         <button class="logout-btn" on:click=on_logout>"Logout"</button>
                     </div>
                     <div class="mail-content">
@@ -65,6 +128,7 @@ pub fn MailLayout() -> impl IntoView {
                             <MailboxSidebar/>
                         </div>
                         <div class="mail-main">
+                            <UndoSendBar/>
                             <Outlet/>
                         </div>
                     </div>
@@ -1,4 +1,6 @@
 use jmap_client::Mailbox;
+use leptos::prelude::*;
+use leptos_router::hooks::use_query_map;
 
 const WELL_KNOWN_ROLES: &[&str] = &["inbox", "drafts", "sent", "junk", "trash", "archive"];
 
@@ -22,13 +24,41 @@ pub fn mailbox_id_to_slug(mailboxes: &[Mailbox], mailbox_id: &str) -> String {
 
 /// Convert a URL slug back to a mailbox ID. Tries role match first, then raw ID.
 pub fn slug_to_mailbox_id(mailboxes: &[Mailbox], slug: &str) -> Option<String> {
-    if WELL_KNOWN_ROLES.contains(&slug) {
-        if let Some(m) = mailboxes.iter().find(|m| m.role.as_deref() == Some(slug)) {
-            return Some(m.id.clone());
-        }
+    if WELL_KNOWN_ROLES.contains(&slug)
+        && let Some(m) = mailboxes.iter().find(|m| m.role.as_deref() == Some(slug))
+    {
+        return Some(m.id.clone());
     }
     if mailboxes.iter().any(|m| m.id == slug) {
         return Some(slug.to_string());
     }
     None
 }
+
+/// The active search from the URL query: (search text, search all mailboxes).
+pub fn use_search() -> Memo<(String, bool)> {
+    let query = use_query_map();
+    Memo::new(move |_| {
+        query.with(|q| {
+            (
+                q.get("q").unwrap_or_default(),
+                q.get("scope").as_deref() == Some("all"),
+            )
+        })
+    })
+}
+
+/// Build the `?q=...&scope=all` suffix that carries a search through
+/// navigation. Empty when there is no search.
+pub fn search_query_string(query: &str, all_mailboxes: bool) -> String {
+    let query = query.trim();
+    if query.is_empty() {
+        return String::new();
+    }
+    let encoded = String::from(js_sys::encode_uri_component(query));
+    if all_mailboxes {
+        format!("?q={encoded}&scope=all")
+    } else {
+        format!("?q={encoded}")
+    }
+}
@@ -0,0 +1,327 @@
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+
+/// Elements removed along with their content.
+const BLOCKED_ELEMENTS: &[&str] = &[
+    "script", "iframe", "frame", "frameset", "object", "embed", "applet", "form", "input",
+    "button", "select", "textarea", "base", "meta",
+];
+
+/// URL attributes the browser fetches automatically when rendering, as
+/// opposed to links the user has to click.
+const AUTOLOAD_ATTRIBUTES: &[&str] = &["src", "srcset", "background", "poster", "lowsrc", "dynsrc"];
+
+/// CSP applied while remote content is blocked. Catches anything the
+/// attribute rewriting misses (CSS `url()` and `@import`, SVG, etc.).
+const BLOCK_REMOTE_CSP: &str = "default-src 'none'; img-src data: blob:; media-src data: blob:; \
+    style-src 'unsafe-inline'; font-src data:";
+
+/// Attributes that hold URLs and need their scheme checked.
+const URL_ATTRIBUTES: &[&str] = &[
+    "href", "src", "action", "formaction", "xlink:href", "background", "poster", "srcset",
+    "lowsrc", "dynsrc", "ping",
+];
+
+#[derive(Debug, Clone)]
+pub struct SanitizedHtml {
+    /// A complete HTML document suitable for an iframe's `srcdoc`.
+    pub html: String,
+    /// Whether any remote resources were removed or blocked.
+    pub blocked_remote: bool,
+}
+
+/// Sanitize an HTML email body for display in a sandboxed iframe.
+///
+/// Parses with the browser's DOMParser (which never runs scripts or loads
+/// resources), strips scripts, forms, frames, event handlers and
+/// `javascript:` URLs, and rewrites `cid:` references using `cid_urls`
+/// (Content-ID → object URL). Unless `allow_remote` is set, remote images,
+/// stylesheets and other auto-loaded resources are blocked too.
+pub fn sanitize_html(
+    html: &str,
+    cid_urls: &HashMap<String, String>,
+    allow_remote: bool,
+) -> Result<SanitizedHtml, JsValue> {
+    let mut blocked_remote = false;
+    let parser = web_sys::DomParser::new()?;
+    let doc = parser.parse_from_string(html, web_sys::SupportedType::TextHtml)?;
+
+    let elements = doc.query_selector_all("*")?;
+    for i in 0..elements.length() {
+        let Some(node) = elements.item(i) else { continue };
+        let el: web_sys::Element = node.unchecked_into();
+        let tag = el.local_name();
+
+        if BLOCKED_ELEMENTS.contains(&tag.as_str()) || is_blocked_link(&el) {
+            el.remove();
+            continue;
+        }
+
+        if !allow_remote {
+            if tag == "link" {
+                blocked_remote = true;
+                el.remove();
+                continue;
+            }
+            if tag == "style" && css_has_remote_url(&el.text_content().unwrap_or_default()) {
+                // Left in place; the CSP stops the fetch
+                blocked_remote = true;
+            }
+        }
+
+        for name in el.get_attribute_names().iter() {
+            let Some(name) = name.as_string() else { continue };
+            let lower = name.to_ascii_lowercase();
+            let value = el.get_attribute(&name).unwrap_or_default();
+
+            if lower.starts_with("on") {
+                el.remove_attribute(&name)?;
+            } else if lower == "style" {
+                let style = value.to_ascii_lowercase();
+                if style.contains("expression(") || style.contains("javascript:") {
+                    el.remove_attribute(&name)?;
+                } else if !allow_remote && css_has_remote_url(&style) {
+                    blocked_remote = true;
+                }
+            } else if !allow_remote
+                && AUTOLOAD_ATTRIBUTES.contains(&lower.as_str())
+                && has_remote_url(&lower, &value)
+            {
+                blocked_remote = true;
+                el.remove_attribute(&name)?;
+            } else if URL_ATTRIBUTES.contains(&lower.as_str()) {
+                if let Some(cid) = strip_scheme(&value, "cid:") {
+                    match cid_urls.get(cid) {
+                        Some(url) => el.set_attribute(&name, url)?,
+                        None => el.remove_attribute(&name)?,
+                    }
+                } else if is_dangerous_url(&value) {
+                    el.remove_attribute(&name)?;
+                }
+            }
+        }
+    }
+
+    if let Some(head) = doc.head() {
+        // Links open in a new tab rather than navigating the iframe
+        let base = doc.create_element("base")?;
+        base.set_attribute("target", "_blank")?;
+        head.prepend_with_node_1(&base)?;
+
+        // Must come first in <head> to apply to everything after it
+        if !allow_remote {
+            let csp = doc.create_element("meta")?;
+            csp.set_attribute("http-equiv", "Content-Security-Policy")?;
+            csp.set_attribute("content", BLOCK_REMOTE_CSP)?;
+            head.prepend_with_node_1(&csp)?;
+        }
+    }
+
+    let root = doc
+        .document_element()
+        .ok_or_else(|| JsValue::from_str("No document element"))?;
+    Ok(SanitizedHtml {
+        html: format!("<!DOCTYPE html>{}", root.outer_html()),
+        blocked_remote,
+    })
+}
+
+fn is_remote_url(url: &str) -> bool {
+    // Like browsers, skip leading controls, drop tabs and newlines anywhere,
+    // and read `\` as `/` (so `\\host` is protocol-relative)
+    let url: String = url
+        .trim_matches(|c: char| c <= ' ' || c == '"' || c == '\'')
+        .chars()
+        .filter(|c| !matches!(c, '\t' | '\n' | '\r'))
+        .map(|c| if c == '\\' { '/' } else { c })
+        .collect::<String>()
+        .to_ascii_lowercase();
+    ["http:", "https:", "ftp:", "//"]
+        .iter()
+        .any(|prefix| url.starts_with(prefix))
+}
+
+/// Check an auto-loaded URL attribute. `srcset` holds a comma-separated
+/// list of "url descriptor" candidates.
+fn has_remote_url(attribute: &str, value: &str) -> bool {
+    if attribute == "srcset" {
+        value
+            .split(',')
+            .filter_map(|candidate| candidate.split_whitespace().next())
+            .any(is_remote_url)
+    } else {
+        is_remote_url(value)
+    }
+}
+
+/// Look for `url(...)` or `@import` references to remote resources in CSS.
+fn css_has_remote_url(css: &str) -> bool {
+    let css = normalize_css(css).to_ascii_lowercase();
+    let in_url = css
+        .split("url(")
+        .skip(1)
+        .any(|rest| is_remote_url(rest.split(')').next().unwrap_or("")));
+    let in_import = css
+        .split("@import")
+        .skip(1)
+        .any(|rest| is_remote_url(rest.trim_start().split([';', ' ']).next().unwrap_or("")));
+    in_url || in_import
+}
+
+/// Drop comments and resolve escapes, so `u\72l(` or `url(/**/http:...)`
+/// reads the way the browser parses it.
+fn normalize_css(css: &str) -> String {
+    let mut out = String::with_capacity(css.len());
+    let mut chars = css.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = ' ';
+                for c in chars.by_ref() {
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+            }
+            '\\' => {
+                // Up to six hex digits and an optional space, or any other
+                // character taken literally
+                let mut hex = String::new();
+                while hex.len() < 6
+                    && let Some(digit) = chars.next_if(char::is_ascii_hexdigit)
+                {
+                    hex.push(digit);
+                }
+                if hex.is_empty() {
+                    out.extend(chars.next());
+                } else {
+                    chars.next_if(|c| c.is_whitespace());
+                    let code = u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32);
+                    out.push(code.unwrap_or(char::REPLACEMENT_CHARACTER));
+                }
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// `<link>` is only kept for stylesheets; other relations (prefetch,
+/// import, etc.) have no place in an email.
+fn is_blocked_link(el: &web_sys::Element) -> bool {
+    el.local_name() == "link"
+        && !el
+            .get_attribute("rel")
+            .is_some_and(|rel| rel.eq_ignore_ascii_case("stylesheet"))
+}
+
+/// Match a URL scheme case-insensitively, returning the rest of the URL.
+fn strip_scheme<'a>(url: &'a str, scheme: &str) -> Option<&'a str> {
+    let url = url.trim();
+    url.get(..scheme.len())
+        .filter(|prefix| prefix.eq_ignore_ascii_case(scheme))
+        .map(|_| &url[scheme.len()..])
+}
+
+fn is_dangerous_url(url: &str) -> bool {
+    // Browsers ignore embedded whitespace and control characters in schemes
+    let normalized: String = url
+        .chars()
+        .filter(|c| !c.is_whitespace() && !c.is_control())
+        .collect::<String>()
+        .to_ascii_lowercase();
+    normalized.starts_with("javascript:")
+        || normalized.starts_with("vbscript:")
+        || (normalized.starts_with("data:") && !normalized.starts_with("data:image/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dangerous_schemes() {
+        for url in [
+            "javascript:alert(1)",
+            "JaVaScRiPt:alert(1)",
+            "  javascript:alert(1)",
+            "java\tscript:alert(1)",
+            "java\nscript:alert(1)",
+            "\u{1}javascript:alert(1)",
+            "jav\u{0}ascript:alert(1)",
+            "vbscript:msgbox(1)",
+            "data:text/html,<script>alert(1)</script>",
+            "DATA:text/html;base64,PHNjcmlwdD4=",
+        ] {
+            assert!(is_dangerous_url(url), "{url:?}");
+        }
+        for url in [
+            "https://example.com/",
+            "mailto:someone@example.com",
+            "data:image/png;base64,iVBORw0KGgo=",
+            "/relative/javascript:",
+            "#top",
+        ] {
+            assert!(!is_dangerous_url(url), "{url:?}");
+        }
+    }
+
+    #[test]
+    fn scheme_stripping() {
+        assert_eq!(strip_scheme("cid:part1@example.com", "cid:"), Some("part1@example.com"));
+        assert_eq!(strip_scheme("  CID:part1", "cid:"), Some("part1"));
+        assert_eq!(strip_scheme("ci", "cid:"), None);
+        assert_eq!(strip_scheme("https://cid:x", "cid:"), None);
+        // Multi-byte characters where the scheme would end don't panic
+        assert_eq!(strip_scheme("cié:x", "cid:"), None);
+    }
+
+    #[test]
+    fn remote_attribute_urls() {
+        for url in [
+            "http://example.com/a.png",
+            "HTTPS://example.com/a.png",
+            "//example.com/a.png",
+            " \u{1}//example.com/a.png",
+            "/\t/example.com/a.png",
+            "\\\\example.com/a.png",
+            "'https://example.com/a.png'",
+            "ftp://example.com/a.png",
+        ] {
+            assert!(has_remote_url("src", url), "{url:?}");
+        }
+        for url in ["cid:part1", "data:image/png;base64,AAAA", "/a.png", "a.png", ""] {
+            assert!(!has_remote_url("src", url), "{url:?}");
+        }
+        assert!(has_remote_url("srcset", "a.png 1x, https://example.com/b.png 2x"));
+        assert!(!has_remote_url("srcset", "a.png 1x, cid:b 2x"));
+    }
+
+    #[test]
+    fn remote_css_urls() {
+        for css in [
+            "background: url(http://example.com/a.png)",
+            "background: URL( 'https://example.com/a.png' )",
+            "background: url(//example.com/a.png)",
+            "background: url(/**/https://example.com/a.png)",
+            r"background: u\72l(https://example.com/a.png)",
+            r"background: url(\68 ttps://example.com/a.png)",
+            r"background: url(h\ttps://example.com/a.png)",
+            "@import 'https://example.com/a.css';",
+            "@import url(https://example.com/a.css);",
+            r"@\69mport 'https://example.com/a.css';",
+        ] {
+            assert!(css_has_remote_url(css), "{css:?}");
+        }
+        for css in [
+            "color: red",
+            "background: url(cid:part1)",
+            "background: url(data:image/png;base64,AAAA)",
+            "/* url(https://example.com/a.png) */ color: red",
+        ] {
+            assert!(!css_has_remote_url(css), "{css:?}");
+        }
+    }
+}
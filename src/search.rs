@@ -0,0 +1,305 @@
+use jmap_client::{EmailFilter, EmailFilterCondition, Operator};
+
+/// Parse a search query into an Email/query filter. Returns `Ok(None)` for
+/// an empty query.
+///
+/// Terms are ANDed together and may be negated with a leading `-`:
+/// `from:`, `to:`, `cc:`, `bcc:`, `subject:`, `body:`, `has:attachment`,
+/// `is:unread`, `is:read`, `is:flagged`, `before:YYYY-MM-DD`,
+/// `after:YYYY-MM-DD`, and free text. Values with spaces can be quoted,
+/// e.g. `subject:"quarterly report"`.
+pub fn parse_search(query: &str) -> Result<Option<EmailFilter>, String> {
+    let mut filters = Vec::new();
+    for token in tokenize(query) {
+        let (negated, term) = match token.strip_prefix('-') {
+            Some(rest) if !rest.is_empty() => (true, rest),
+            _ => (false, token.as_str()),
+        };
+        let condition = parse_term(term)?;
+        filters.push(if negated {
+            EmailFilter::negate(condition.into())
+        } else {
+            condition.into()
+        });
+    }
+
+    if filters.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(EmailFilter::and(filters)))
+    }
+}
+
+/// Split on whitespace outside double quotes. Quote characters are removed,
+/// so `from:"Jane Doe"` becomes `from:Jane Doe`.
+fn tokenize(query: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in query.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+fn parse_term(term: &str) -> Result<EmailFilterCondition, String> {
+    let mut condition = EmailFilterCondition::default();
+    let Some((key, value)) = term.split_once(':') else {
+        condition.text = Some(term.to_string());
+        return Ok(condition);
+    };
+
+    let require_value = || {
+        if value.is_empty() {
+            Err(format!("Missing value for \"{key}:\""))
+        } else {
+            Ok(Some(value.to_string()))
+        }
+    };
+
+    match key.to_ascii_lowercase().as_str() {
+        "from" => condition.from = require_value()?,
+        "to" => condition.to = require_value()?,
+        "cc" => condition.cc = require_value()?,
+        "bcc" => condition.bcc = require_value()?,
+        "subject" => condition.subject = require_value()?,
+        "body" => condition.body = require_value()?,
+        "has" => match value.to_ascii_lowercase().as_str() {
+            "attachment" => condition.has_attachment = Some(true),
+            _ => return Err(format!("Unknown search term \"has:{value}\"")),
+        },
+        "is" => match value.to_ascii_lowercase().as_str() {
+            "unread" => condition.not_keyword = Some("$seen".to_string()),
+            "read" => condition.has_keyword = Some("$seen".to_string()),
+            "flagged" => condition.has_keyword = Some("$flagged".to_string()),
+            _ => return Err(format!("Unknown search term \"is:{value}\"")),
+        },
+        "before" => condition.before = Some(parse_date(value)?),
+        "after" => condition.after = Some(parse_date(value)?),
+        // Not a search key (e.g. a URL or time): treat as free text
+        _ => condition.text = Some(term.to_string()),
+    }
+    Ok(condition)
+}
+
+/// Convert `YYYY-MM-DD` to a UTCDate at midnight.
+fn parse_date(value: &str) -> Result<String, String> {
+    let invalid = || format!("Invalid date \"{value}\", expected YYYY-MM-DD");
+    let mut parts = value.split('-');
+    let (Some(year), Some(month), Some(day), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(invalid());
+    };
+    let (Ok(year), Ok(month), Ok(day)) =
+        (year.parse::<u32>(), month.parse::<u32>(), day.parse::<u32>())
+    else {
+        return Err(invalid());
+    };
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || year < 1000 {
+        return Err(invalid());
+    }
+    Ok(format!("{year:04}-{month:02}-{day:02}T00:00:00Z"))
+}
+
+/// Whether the filter searches text that SearchSnippet/get can highlight.
+/// Negated terms are ignored since they can't match anything shown.
+pub fn has_text_filter(filter: &EmailFilter) -> bool {
+    match filter {
+        EmailFilter::Operator(op) => {
+            op.operator != Operator::Not && op.conditions.iter().any(has_text_filter)
+        }
+        EmailFilter::Condition(c) => c.text.is_some() || c.subject.is_some() || c.body.is_some(),
+    }
+}
+
+/// Split a search snippet into (text, highlighted) runs. Snippets are HTML
+/// with matches in `<mark>`; every other tag is kept as literal text and
+/// entities are decoded, so the runs are safe to render as text nodes.
+pub fn snippet_segments(snippet: &str) -> Vec<(String, bool)> {
+    let mut segments = Vec::new();
+    let mut rest = snippet;
+    while let Some(start) = rest.find("<mark>") {
+        if start > 0 {
+            segments.push((decode_entities(&rest[..start]), false));
+        }
+        let after = &rest[start + "<mark>".len()..];
+        let end = after.find("</mark>").unwrap_or(after.len());
+        segments.push((decode_entities(&after[..end]), true));
+        rest = after.get(end + "</mark>".len()..).unwrap_or("");
+    }
+    if !rest.is_empty() {
+        segments.push((decode_entities(rest), false));
+    }
+    segments
+}
+
+fn decode_entities(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let decoded = rest.find(';').and_then(|semi| {
+            let c = match &rest[1..semi] {
+                "amp" => '&',
+                "lt" => '<',
+                "gt" => '>',
+                "quot" => '"',
+                "apos" => '\'',
+                "nbsp" => '\u{a0}',
+                entity => {
+                    let code = match entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X")) {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                        None => entity.strip_prefix('#')?.parse().ok()?,
+                    };
+                    char::from_u32(code)?
+                }
+            };
+            Some((c, semi + 1))
+        });
+        match decoded {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn condition(f: impl FnOnce(&mut EmailFilterCondition)) -> EmailFilter {
+        let mut condition = EmailFilterCondition::default();
+        f(&mut condition);
+        condition.into()
+    }
+
+    #[test]
+    fn empty_query() {
+        assert_eq!(parse_search(""), Ok(None));
+        assert_eq!(parse_search("   "), Ok(None));
+    }
+
+    #[test]
+    fn quoted_phrases() {
+        assert_eq!(tokenize(r#"from:"Jane Doe" hello"#), ["from:Jane Doe", "hello"]);
+        assert_eq!(
+            parse_search(r#"subject:"quarterly report""#),
+            Ok(Some(condition(|c| c.subject = Some("quarterly report".to_string()))))
+        );
+    }
+
+    #[test]
+    fn terms_are_anded_and_negated() {
+        assert_eq!(
+            parse_search("is:unread -from:bob"),
+            Ok(Some(EmailFilter::and(vec![
+                condition(|c| c.not_keyword = Some("$seen".to_string())),
+                EmailFilter::negate(condition(|c| c.from = Some("bob".to_string()))),
+            ])))
+        );
+        // A lone `-` is free text, not an empty negation
+        assert_eq!(
+            parse_search("-"),
+            Ok(Some(condition(|c| c.text = Some("-".to_string()))))
+        );
+    }
+
+    #[test]
+    fn keys() {
+        assert_eq!(
+            parse_search("HAS:Attachment"),
+            Ok(Some(condition(|c| c.has_attachment = Some(true))))
+        );
+        assert!(parse_search("has:pictures").is_err());
+        assert!(parse_search("is:archived").is_err());
+        assert!(parse_search("from:").is_err());
+        // Unknown keys are searched as text
+        assert_eq!(
+            parse_search("https://example.com"),
+            Ok(Some(condition(|c| c.text = Some("https://example.com".to_string()))))
+        );
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(parse_date("2024-1-31"), Ok("2024-01-31T00:00:00Z".to_string()));
+        for bad in ["", "2024-13-01", "2024-01-32", "2024-01", "2024-01-01-01", "99-01-01", "x"] {
+            assert!(parse_date(bad).is_err(), "{bad}");
+        }
+        assert!(parse_search("before:yesterday").is_err());
+    }
+
+    #[test]
+    fn text_filters() {
+        let filter = |query| parse_search(query).unwrap().unwrap();
+        assert!(has_text_filter(&filter("hello")));
+        assert!(has_text_filter(&filter("is:unread subject:report")));
+        assert!(has_text_filter(&filter("body:report -hello")));
+        assert!(!has_text_filter(&filter("is:unread from:bob")));
+        assert!(!has_text_filter(&filter("-hello")));
+    }
+
+    fn text(s: &str) -> (String, bool) {
+        (s.to_string(), false)
+    }
+
+    fn mark(s: &str) -> (String, bool) {
+        (s.to_string(), true)
+    }
+
+    #[test]
+    fn snippet_marks() {
+        assert_eq!(snippet_segments(""), []);
+        assert_eq!(
+            snippet_segments("the <mark>quarterly</mark> report"),
+            [text("the "), mark("quarterly"), text(" report")]
+        );
+        // An unclosed mark runs to the end; a stray close is literal text
+        assert_eq!(snippet_segments("a <mark>b"), [text("a "), mark("b")]);
+        assert_eq!(snippet_segments("a</mark>b"), [text("a</mark>b")]);
+        // Nested marks don't nest: the inner tags come out as text
+        assert_eq!(
+            snippet_segments("<mark><mark>a</mark></mark>"),
+            [mark("<mark>a"), text("</mark>")]
+        );
+    }
+
+    #[test]
+    fn snippet_entities_and_tags_are_text() {
+        assert_eq!(
+            snippet_segments("&lt;b&gt; <mark>Q&amp;A</mark> &#39;&#x41;&quot;"),
+            [text("<b> "), mark("Q&A"), text(" 'A\"")]
+        );
+        // Tags other than mark are never interpreted
+        assert_eq!(
+            snippet_segments("<script>alert(1)</script><mark><img src=x></mark>"),
+            [text("<script>alert(1)</script>"), mark("<img src=x>")]
+        );
+        // Unknown or malformed entities are left as they are
+        assert_eq!(
+            snippet_segments("a &bogus; b & c &#xZZ; &#1114112;"),
+            [text("a &bogus; b & c &#xZZ; &#1114112;")]
+        );
+    }
+}
@@ -1,8 +1,62 @@
-use jmap_client::{Identity, JmapClient, Mailbox};
+use crate::outbox::{HeldForward, OutboxEntry, UndoableSend};
+use crate::store::EntityStore;
+use jmap_client::{EmailSubmission, Identity, JmapClient, Mailbox};
 use leptos::prelude::*;
+use serde::{Deserialize, Serialize};
 use web_sys::window;
 
 const STORAGE_KEY: &str = "jmap_credentials";
+const REMOTE_CONTENT_STORAGE_KEY: &str = "jmap_remote_content_allowlist";
+const UNDO_SEND_STORAGE_KEY: &str = "jmap_undo_send_seconds";
+
+/// Senders and domains whose emails may load remote images and styles.
+/// Addresses and domains are stored lowercased.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct RemoteContentAllowlist {
+    #[serde(default)]
+    pub senders: Vec<String>,
+    #[serde(default)]
+    pub domains: Vec<String>,
+}
+
+impl RemoteContentAllowlist {
+    pub fn allows(&self, sender: &str) -> bool {
+        let sender = sender.to_ascii_lowercase();
+        self.senders.contains(&sender)
+            || sender_domain(&sender).is_some_and(|domain| self.domains.iter().any(|d| d == domain))
+    }
+
+    pub fn allow_sender(&mut self, sender: &str) {
+        let sender = sender.to_ascii_lowercase();
+        if !self.senders.contains(&sender) {
+            self.senders.push(sender);
+        }
+    }
+
+    pub fn allow_domain(&mut self, domain: &str) {
+        let domain = domain.to_ascii_lowercase();
+        if !self.domains.contains(&domain) {
+            self.domains.push(domain);
+        }
+    }
+}
+
+/// The domain part of an email address.
+pub fn sender_domain(sender: &str) -> Option<&str> {
+    sender.rsplit_once('@').map(|(_, domain)| domain).filter(|d| !d.is_empty())
+}
+
+/// How the inline compose form responds to `reply_to_email`.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum ReplyMode {
+    #[default]
+    Reply,
+    ReplyAll,
+    /// Quote the body and re-attach the original attachments.
+    Forward,
+    /// Attach the original message as message/rfc822.
+    ForwardAsAttachment,
+}
 
 #[derive(Clone, Copy)]
 pub struct AppState {
@@ -10,12 +64,30 @@ pub struct AppState {
     pub mailboxes: RwSignal<Vec<Mailbox>>,
     pub selected_mailbox: RwSignal<Option<String>>,
     pub identities: RwSignal<Vec<Identity>>,
+    pub identity_state: RwSignal<Option<String>>,
     pub reply_to_email: RwSignal<Option<String>>,
-    pub reply_all: RwSignal<bool>,
+    pub reply_mode: RwSignal<ReplyMode>,
     pub email_state: RwSignal<Option<String>>,
     pub mailbox_state: RwSignal<Option<String>>,
     pub email_refresh_trigger: RwSignal<u64>,
     pub auto_login_done: RwSignal<bool>,
+    /// Showing cached mail without a server connection (still connecting,
+    /// or the network is down).
+    pub offline: RwSignal<bool>,
+    pub remote_content_allowlist: RwSignal<RemoteContentAllowlist>,
+    pub store: EntityStore,
+    /// Messages waiting for a connection to be sent.
+    pub outbox: RwSignal<Vec<OutboxEntry>>,
+    /// Seconds each send is held so it can be undone; 0 sends immediately.
+    pub undo_send_seconds: RwSignal<u64>,
+    /// The last message sent, while it can still be undone.
+    pub pending_undo: RwSignal<Option<UndoableSend>>,
+    /// Forwards waiting for their held submission to be released.
+    pub held_forwards: RwSignal<Vec<HeldForward>>,
+    /// Recent submissions, newest first, for the Sending view.
+    pub submissions: RwSignal<Vec<EmailSubmission>>,
+    /// EmailSubmission state, set once the Sending view has loaded.
+    pub submission_state: RwSignal<Option<String>>,
     pub sse_abort: StoredValue<Option<web_sys::AbortController>, LocalStorage>,
 }
 
@@ -26,12 +98,22 @@ impl AppState {
             mailboxes: RwSignal::new(vec![]),
             selected_mailbox: RwSignal::new(None),
             identities: RwSignal::new(vec![]),
+            identity_state: RwSignal::new(None),
             reply_to_email: RwSignal::new(None),
-            reply_all: RwSignal::new(false),
+            reply_mode: RwSignal::new(ReplyMode::Reply),
             email_state: RwSignal::new(None),
             mailbox_state: RwSignal::new(None),
             email_refresh_trigger: RwSignal::new(0),
             auto_login_done: RwSignal::new(false),
+            offline: RwSignal::new(false),
+            remote_content_allowlist: RwSignal::new(load_remote_content_allowlist()),
+            store: EntityStore::new(),
+            outbox: RwSignal::new(vec![]),
+            undo_send_seconds: RwSignal::new(load_undo_send_seconds()),
+            pending_undo: RwSignal::new(None),
+            held_forwards: RwSignal::new(vec![]),
+            submissions: RwSignal::new(vec![]),
+            submission_state: RwSignal::new(None),
             sse_abort: StoredValue::new_local(None),
         }
     }
@@ -48,12 +130,21 @@ impl AppState {
         self.mailboxes.set(vec![]);
         self.selected_mailbox.set(None);
         self.identities.set(vec![]);
+        self.identity_state.set(None);
         self.reply_to_email.set(None);
-        self.reply_all.set(false);
+        self.reply_mode.set(ReplyMode::Reply);
         self.email_state.set(None);
         self.mailbox_state.set(None);
         self.email_refresh_trigger.set(0);
+        self.offline.set(false);
+        self.store.clear();
+        self.outbox.set(vec![]);
+        self.pending_undo.set(None);
+        self.held_forwards.set(vec![]);
+        self.submissions.set(vec![]);
+        self.submission_state.set(None);
         clear_saved_credentials();
+        leptos::task::spawn_local(crate::cache::clear());
     }
 }
 
@@ -84,3 +175,34 @@ pub fn clear_saved_credentials() {
         let _ = storage.remove_item(STORAGE_KEY);
     }
 }
+
+pub fn save_remote_content_allowlist(allowlist: &RemoteContentAllowlist) {
+    let Some(storage) = window().and_then(|w| w.local_storage().ok().flatten()) else {
+        return;
+    };
+    if let Ok(json) = serde_json::to_string(allowlist) {
+        let _ = storage.set_item(REMOTE_CONTENT_STORAGE_KEY, &json);
+    }
+}
+
+fn load_remote_content_allowlist() -> RemoteContentAllowlist {
+    window()
+        .and_then(|w| w.local_storage().ok().flatten())
+        .and_then(|storage| storage.get_item(REMOTE_CONTENT_STORAGE_KEY).ok().flatten())
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_undo_send_seconds(seconds: u64) {
+    if let Some(storage) = window().and_then(|w| w.local_storage().ok().flatten()) {
+        let _ = storage.set_item(UNDO_SEND_STORAGE_KEY, &seconds.to_string());
+    }
+}
+
+fn load_undo_send_seconds() -> u64 {
+    window()
+        .and_then(|w| w.local_storage().ok().flatten())
+        .and_then(|storage| storage.get_item(UNDO_SEND_STORAGE_KEY).ok().flatten())
+        .and_then(|raw| raw.parse().ok())
+        .unwrap_or(0)
+}
@@ -0,0 +1,310 @@
+use crate::state::AppState;
+use jmap_client::{Email, Thread};
+use leptos::prelude::*;
+use leptos::task::spawn_local;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Most emails kept in the offline cache, newest first.
+const CACHED_EMAILS: usize = 2000;
+/// How many of the most recent threads keep their bodies in the offline
+/// cache; older emails are cached without them.
+const CACHED_THREAD_BODIES: usize = 100;
+
+/// The part of an [`EntityStore`] kept in the offline cache.
+#[derive(Default, Serialize, Deserialize)]
+pub struct StoreSnapshot {
+    pub email_state: Option<String>,
+    pub thread_state: Option<String>,
+    pub emails: Vec<Email>,
+    pub threads: HashMap<String, Vec<String>>,
+}
+
+/// Normalized cache of emails and threads by ID, shared by every view.
+///
+/// Emails are merged property by property, so a row loaded by the list
+/// gains its body once the thread is opened. `sync_store` keeps the cache
+/// coherent with the server using Email/changes and Thread/changes.
+///
+/// Mailboxes aren't held here: the whole list is always loaded, so it stays
+/// in `AppState::mailboxes`, kept current by `sync_mailboxes`.
+#[derive(Clone, Copy)]
+pub struct EntityStore {
+    emails: RwSignal<HashMap<String, Email>>,
+    /// Thread ID → email IDs, oldest first.
+    threads: RwSignal<HashMap<String, Vec<String>>>,
+    /// States the cache is known to be current to. Kept at the oldest state
+    /// merged since the last sync, so replaying changes never misses any.
+    email_state: StoredValue<Option<String>>,
+    thread_state: StoredValue<Option<String>>,
+}
+
+impl EntityStore {
+    pub fn new() -> Self {
+        Self {
+            emails: RwSignal::new(HashMap::new()),
+            threads: RwSignal::new(HashMap::new()),
+            email_state: StoredValue::new(None),
+            thread_state: StoredValue::new(None),
+        }
+    }
+
+    pub fn clear(&self) {
+        self.emails.set(HashMap::new());
+        self.threads.set(HashMap::new());
+        self.email_state.set_value(None);
+        self.thread_state.set_value(None);
+    }
+
+    /// Subscribe to every change to the cached emails and threads.
+    pub fn track(&self) {
+        self.emails.track();
+        self.threads.track();
+    }
+
+    /// Copy out the store for the offline cache: the newest emails, with
+    /// bodies (and thread lists) only for the most recent threads.
+    pub fn snapshot(&self) -> StoreSnapshot {
+        let (emails, threads) = self.emails.with_untracked(|emails| {
+            self.threads.with_untracked(|threads| {
+                let mut newest: Vec<&Email> = emails.values().collect();
+                newest.sort_by(|a, b| b.received_at.cmp(&a.received_at));
+                newest.truncate(CACHED_EMAILS);
+
+                let mut recent: HashMap<String, Vec<String>> = HashMap::new();
+                for email in &newest {
+                    if recent.len() == CACHED_THREAD_BODIES {
+                        break;
+                    }
+                    if let Some(thread_id) = &email.thread_id
+                        && let Some(email_ids) = threads.get(thread_id)
+                    {
+                        recent.entry(thread_id.clone()).or_insert_with(|| email_ids.clone());
+                    }
+                }
+
+                let emails = newest
+                    .into_iter()
+                    .map(|email| {
+                        let keep_body = email
+                            .thread_id
+                            .as_ref()
+                            .is_some_and(|t| recent.contains_key(t));
+                        if keep_body {
+                            return email.clone();
+                        }
+                        Email {
+                            text_body: None,
+                            html_body: None,
+                            attachments: None,
+                            body_values: None,
+                            ..email.clone()
+                        }
+                    })
+                    .collect();
+                (emails, recent)
+            })
+        });
+        StoreSnapshot {
+            email_state: self.email_state.get_value(),
+            thread_state: self.thread_state.get_value(),
+            emails,
+            threads,
+        }
+    }
+
+    pub fn restore(&self, snapshot: StoreSnapshot) {
+        self.emails.set(
+            snapshot
+                .emails
+                .into_iter()
+                .filter_map(|e| Some((e.id.clone()?, e)))
+                .collect(),
+        );
+        self.threads.set(snapshot.threads);
+        self.email_state.set_value(snapshot.email_state);
+        self.thread_state.set_value(snapshot.thread_state);
+    }
+
+    /// Add emails the store doesn't hold yet, leaving existing (possibly
+    /// newer) copies alone.
+    pub fn add_missing(&self, emails: &[Email]) {
+        self.emails.update(|cache| {
+            for email in emails {
+                if let Some(id) = &email.id {
+                    cache.entry(id.clone()).or_insert_with(|| email.clone());
+                }
+            }
+        });
+    }
+
+    /// Look up an email without tracking.
+    pub fn email(&self, id: &str) -> Option<Email> {
+        self.emails.with_untracked(|emails| emails.get(id).cloned())
+    }
+
+    /// Look up an email only if its body values have been fetched.
+    pub fn email_with_body(&self, id: &str) -> Option<Email> {
+        self.email(id).filter(|e| e.body_values.is_some())
+    }
+
+    /// Reactive keyword check, so rows and cards update wherever it changes.
+    pub fn has_keyword(&self, id: &str, keyword: &str) -> bool {
+        self.emails
+            .with(|emails| emails.get(id).is_some_and(|e| e.has_keyword(keyword)))
+    }
+
+    /// All emails in a thread, if the thread and every email's body are
+    /// cached.
+    pub fn thread_with_bodies(&self, thread_id: &str) -> Option<Vec<Email>> {
+        let email_ids = self
+            .threads
+            .with_untracked(|threads| threads.get(thread_id).cloned())?;
+        email_ids
+            .iter()
+            .map(|id| self.email_with_body(id))
+            .collect()
+    }
+
+    /// Merge emails fetched at `state` into the cache.
+    pub fn merge_emails(&self, emails: &[Email], state: &str) {
+        self.emails.update(|cache| {
+            for email in emails {
+                let Some(id) = email.id.clone() else { continue };
+                let merged = match cache.remove(&id) {
+                    Some(existing) => merge_email(existing, email),
+                    None => email.clone(),
+                };
+                cache.insert(id, merged);
+            }
+        });
+        if self.email_state.with_value(|s| s.is_none()) && !state.is_empty() {
+            self.email_state.set_value(Some(state.to_string()));
+        }
+    }
+
+    pub fn merge_threads(&self, threads: &[Thread], state: &str) {
+        self.threads.update(|cache| {
+            for thread in threads {
+                cache.insert(thread.id.clone(), thread.email_ids.clone());
+            }
+        });
+        if self.thread_state.with_value(|s| s.is_none()) && !state.is_empty() {
+            self.thread_state.set_value(Some(state.to_string()));
+        }
+    }
+
+    pub fn set_keyword(&self, ids: &[String], keyword: &str, enabled: bool) {
+        self.emails.update(|cache| {
+            for id in ids {
+                if let Some(email) = cache.get_mut(id) {
+                    email.set_keyword(keyword, enabled);
+                }
+            }
+        });
+    }
+}
+
+/// Overlay the properties present in `update` onto `existing`.
+fn merge_email(existing: Email, update: &Email) -> Email {
+    let (Ok(mut merged), Ok(serde_json::Value::Object(update_props))) =
+        (serde_json::to_value(&existing), serde_json::to_value(update))
+    else {
+        return update.clone();
+    };
+    if let Some(merged) = merged.as_object_mut() {
+        merged.extend(update_props.into_iter().filter(|(_, v)| !v.is_null()));
+    }
+    serde_json::from_value(merged).unwrap_or(existing)
+}
+
+/// Set a keyword in the store straight away, then persist it, reverting the
+/// local change if the server rejects it.
+pub fn set_keyword_optimistic(
+    state: AppState,
+    ids: Vec<String>,
+    keyword: &'static str,
+    enabled: bool,
+) {
+    let Some(client) = state.client.get_untracked() else { return };
+    if ids.is_empty() {
+        return;
+    }
+    state.store.set_keyword(&ids, keyword, enabled);
+    spawn_local(async move {
+        if client.set_keyword(&ids, keyword, enabled).await.is_err() {
+            state.store.set_keyword(&ids, keyword, !enabled);
+        }
+    });
+}
+
+/// Apply Email/changes and Thread/changes since the cached states. Updated
+/// emails have their keywords and mailboxes refetched, destroyed ones are
+/// dropped, and changed threads are evicted so they reload when opened.
+/// Clears the cache if the server can't calculate the changes.
+pub async fn sync_store(state: AppState) {
+    let Some(client) = state.client.get_untracked() else {
+        return;
+    };
+    let store = state.store;
+
+    if let Some(mut since) = store.email_state.get_value() {
+        loop {
+            let mut batch = client.batch();
+            let changes_call = batch.email_changes(&since);
+            let updated_ids = changes_call.reference("/updated");
+            let updated_call = batch.email_get(updated_ids, &["id", "keywords", "mailboxIds"]);
+            let Ok(response) = batch.send().await else { return };
+            let (Ok(changes), Ok(updated)) =
+                (response.get(&changes_call), response.get(&updated_call))
+            else {
+                store.clear();
+                return;
+            };
+
+            // Only refresh emails we hold; others load fresh when needed
+            let cached: Vec<Email> = store.emails.with_untracked(|cache| {
+                updated
+                    .list
+                    .into_iter()
+                    .filter(|e| e.id.as_ref().is_some_and(|id| cache.contains_key(id)))
+                    .collect()
+            });
+            store.merge_emails(&cached, "");
+            store.emails.update(|cache| {
+                for id in &changes.destroyed {
+                    cache.remove(id);
+                }
+            });
+
+            since = changes.new_state;
+            if !changes.has_more_changes {
+                break;
+            }
+        }
+        store.email_state.set_value(Some(since));
+    }
+
+    if let Some(mut since) = store.thread_state.get_value() {
+        loop {
+            let mut batch = client.batch();
+            let changes_call = batch.thread_changes(&since);
+            let Ok(changes) = batch.send().await.and_then(|r| r.get(&changes_call)) else {
+                store.threads.set(HashMap::new());
+                store.thread_state.set_value(None);
+                return;
+            };
+            store.threads.update(|cache| {
+                for id in changes.updated.iter().chain(&changes.destroyed) {
+                    cache.remove(id);
+                }
+            });
+
+            since = changes.new_state;
+            if !changes.has_more_changes {
+                break;
+            }
+        }
+        store.thread_state.set_value(Some(since));
+    }
+}
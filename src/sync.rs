@@ -1,5 +1,7 @@
+use crate::outbox::mark_released_forwards;
 use crate::state::AppState;
-use jmap_client::StateChange;
+use crate::store::sync_store;
+use jmap_client::{EmailSubmission, EmailSubmissionFilter, JmapError, StateChange};
 use leptos::prelude::*;
 use leptos::task::spawn_local;
 
@@ -15,22 +17,42 @@ pub fn handle_state_change(state: AppState, change: StateChange) {
 
     let mailbox_changed = type_changes.contains_key("Mailbox");
     let email_changed = type_changes.contains_key("Email");
+    let thread_changed = type_changes.contains_key("Thread");
+    let submission_changed = type_changes.contains_key("EmailSubmission");
+    let identity_changed = type_changes.contains_key("Identity");
 
     if mailbox_changed {
-        spawn_local(async move {
-            let Some(client) = state.client.get_untracked() else {
-                return;
-            };
-            if let Ok((mailboxes, mailbox_state)) = client.get_mailboxes().await {
-                state.mailboxes.set(mailboxes);
-                state.mailbox_state.set(Some(mailbox_state));
-            }
-        });
+        let new_mailbox_state = type_changes.get("Mailbox").cloned();
+        // Nothing to do if we've already caught up (e.g. our own change)
+        if new_mailbox_state != state.mailbox_state.get_untracked() {
+            spawn_local(sync_mailboxes(state));
+        }
+    }
+
+    if identity_changed {
+        let new_identity_state = type_changes.get("Identity").cloned();
+        if new_identity_state != state.identity_state.get_untracked() {
+            spawn_local(sync_identities(state));
+        }
+    }
+
+    if email_changed || thread_changed {
+        spawn_local(sync_store(state));
+    }
+
+    if submission_changed {
+        if !state.held_forwards.with_untracked(|held| held.is_empty()) {
+            spawn_local(mark_released_forwards(state));
+        }
+        let new_submission_state = type_changes.get("EmailSubmission").cloned();
+        if new_submission_state != state.submission_state.get_untracked() {
+            spawn_local(sync_submissions(state));
+        }
     }
 
     if email_changed {
         let new_email_state = type_changes.get("Email").cloned();
-        // Bump the refresh trigger so the email list LocalResource re-runs
+        // Bump the refresh trigger so the email list fetches the changes
         state
             .email_refresh_trigger
             .update(|v| *v = v.wrapping_add(1));
@@ -40,3 +62,155 @@ pub fn handle_state_change(state: AppState, change: StateChange) {
         }
     }
 }
+
+/// Apply Mailbox/changes since the last known state to the mailbox list,
+/// falling back to a full reload if the server can't calculate them.
+pub async fn sync_mailboxes(state: AppState) {
+    let Some(client) = state.client.get_untracked() else {
+        return;
+    };
+    let Some(since_state) = state.mailbox_state.get_untracked() else {
+        reload_mailboxes(state).await;
+        return;
+    };
+    let mut mailboxes = state.mailboxes.get_untracked();
+    match client.sync_mailboxes(&mut mailboxes, &since_state).await {
+        Ok(new_state) => {
+            state.mailboxes.set(mailboxes);
+            state.mailbox_state.set(Some(new_state));
+        }
+        Err(_) => reload_mailboxes(state).await,
+    }
+}
+
+/// Refetch the full mailbox list.
+pub async fn reload_mailboxes(state: AppState) {
+    let Some(client) = state.client.get_untracked() else {
+        return;
+    };
+    if let Ok((mailboxes, mailbox_state)) = client.get_mailboxes().await {
+        state.mailboxes.set(mailboxes);
+        state.mailbox_state.set(Some(mailbox_state));
+    }
+}
+
+/// Apply Identity/changes since the last known state to the identity list,
+/// falling back to a full reload if the server can't calculate them.
+pub async fn sync_identities(state: AppState) {
+    let Some(client) = state.client.get_untracked() else {
+        return;
+    };
+    let Some(since_state) = state.identity_state.get_untracked() else {
+        reload_identities(state).await;
+        return;
+    };
+    let mut identities = state.identities.get_untracked();
+    match client.sync_identities(&mut identities, &since_state).await {
+        Ok(new_state) => {
+            state.identities.set(identities);
+            state.identity_state.set(Some(new_state));
+        }
+        Err(_) => reload_identities(state).await,
+    }
+}
+
+/// Refetch all identities.
+pub async fn reload_identities(state: AppState) {
+    let Some(client) = state.client.get_untracked() else {
+        return;
+    };
+    if let Ok((identities, identity_state)) = client.get_identities().await {
+        state.identities.set(identities);
+        state.identity_state.set(Some(identity_state));
+    }
+}
+
+/// How many recent submissions the Sending view shows.
+const SUBMISSION_LIMIT: u64 = 50;
+
+/// Properties of a submission's email shown in the Sending view.
+const SUBMISSION_EMAIL_PROPERTIES: &[&str] = &["id", "threadId", "subject", "to"];
+
+/// Load the most recent submissions along with their emails' subjects.
+pub async fn load_submissions(state: AppState) -> Result<(), JmapError> {
+    let client = state
+        .client
+        .get_untracked()
+        .ok_or_else(|| JmapError::Api("Not connected".to_string()))?;
+    let mut batch = client.batch();
+    let query_call = batch.email_submission_query(
+        &EmailSubmissionFilter::default(),
+        Some(SUBMISSION_LIMIT),
+    );
+    let get_call = batch.email_submission_get(query_call.reference("/ids"));
+    let email_ids = get_call.reference("/list/*/emailId");
+    let email_call = batch.email_get(email_ids, SUBMISSION_EMAIL_PROPERTIES);
+    let response = batch.send().await?;
+    // A failed query fails the get too, so report the query's error
+    response.get(&query_call)?;
+    let submissions = response.get(&get_call)?;
+    if let Ok(emails) = response.get(&email_call) {
+        state.store.merge_emails(&emails.list, &emails.state);
+    }
+
+    let mut list = submissions.list;
+    sort_submissions(&mut list);
+    state.submissions.set(list);
+    state.submission_state.set(Some(submissions.state));
+    Ok(())
+}
+
+/// Apply EmailSubmission/changes to the loaded submissions, so delivery
+/// status updates as the server reports it. Does nothing until the Sending
+/// view has loaded them; reloads if the changes can't be calculated.
+pub async fn sync_submissions(state: AppState) {
+    let Some(client) = state.client.get_untracked() else {
+        return;
+    };
+    let Some(mut since) = state.submission_state.get_untracked() else {
+        return;
+    };
+
+    loop {
+        let mut batch = client.batch();
+        let changes_call = batch.email_submission_changes(&since);
+        let created_call = batch.email_submission_get(changes_call.reference("/created"));
+        let updated_call = batch.email_submission_get(changes_call.reference("/updated"));
+        let email_ids = created_call.reference("/list/*/emailId");
+        let email_call = batch.email_get(email_ids, SUBMISSION_EMAIL_PROPERTIES);
+        let Ok(response) = batch.send().await else { return };
+        let (Ok(changes), Ok(created), Ok(updated)) = (
+            response.get(&changes_call),
+            response.get(&created_call),
+            response.get(&updated_call),
+        ) else {
+            let _ = load_submissions(state).await;
+            return;
+        };
+        if let Ok(emails) = response.get(&email_call) {
+            state.store.merge_emails(&emails.list, "");
+        }
+
+        state.submissions.update(|list| {
+            list.retain(|s| !changes.destroyed.contains(&s.id));
+            for submission in created.list.into_iter().chain(updated.list) {
+                match list.iter_mut().find(|s| s.id == submission.id) {
+                    Some(existing) => *existing = submission,
+                    None => list.push(submission),
+                }
+            }
+            sort_submissions(list);
+        });
+
+        since = changes.new_state;
+        if !changes.has_more_changes {
+            break;
+        }
+    }
+    state.submission_state.set(Some(since));
+}
+
+/// Newest first. `sendAt` is UTC, so the strings sort chronologically.
+fn sort_submissions(list: &mut [EmailSubmission]) {
+    list.sort_by(|a, b| b.send_at.cmp(&a.send_at));
+}
@@ -0,0 +1,75 @@
+use jmap_client::{BlobUpload, JmapClient};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+
+/// Upload a browser `File` as a blob, reporting progress as a fraction in
+/// 0.0..=1.0. Uses XMLHttpRequest rather than fetch since fetch has no
+/// upload progress events.
+pub async fn upload_file(
+    client: &JmapClient,
+    file: &web_sys::File,
+    on_progress: impl Fn(f64) + 'static,
+) -> Result<BlobUpload, String> {
+    let size = file.size() as u64;
+    if let Some(core) = client.session().core_capabilities()
+        && core.max_size_upload > 0
+        && size > core.max_size_upload
+    {
+        return Err(jmap_client::JmapError::UploadTooLarge {
+            size,
+            max: core.max_size_upload,
+        }
+        .to_string());
+    }
+
+    let content_type = match file.type_() {
+        t if t.is_empty() => "application/octet-stream".to_string(),
+        t => t,
+    };
+
+    let xhr = web_sys::XmlHttpRequest::new().map_err(js_error)?;
+    xhr.open("POST", &client.upload_url()).map_err(js_error)?;
+    xhr.set_request_header("Authorization", client.auth_header())
+        .map_err(js_error)?;
+    xhr.set_request_header("Content-Type", &content_type)
+        .map_err(js_error)?;
+
+    let progress = Closure::<dyn FnMut(web_sys::ProgressEvent)>::new(
+        move |ev: web_sys::ProgressEvent| {
+            if ev.length_computable() && ev.total() > 0.0 {
+                on_progress(ev.loaded() / ev.total());
+            }
+        },
+    );
+    if let Ok(upload) = xhr.upload() {
+        upload.set_onprogress(Some(progress.as_ref().unchecked_ref()));
+    }
+
+    // Resolve on load, reject on network error or abort
+    let done = js_sys::Promise::new(&mut |resolve, reject| {
+        xhr.set_onload(Some(&resolve));
+        xhr.set_onerror(Some(&reject));
+        xhr.set_onabort(Some(&reject));
+    });
+
+    xhr.send_with_opt_blob(Some(file)).map_err(js_error)?;
+    let result = JsFuture::from(done).await;
+    drop(progress);
+    result.map_err(|_| "Upload failed: network error".to_string())?;
+
+    match xhr.status().map_err(js_error)? {
+        200..=299 => {}
+        401 => return Err("Authentication failed".to_string()),
+        status => return Err(format!("Upload failed with status {status}")),
+    }
+
+    let text = xhr
+        .response_text()
+        .map_err(js_error)?
+        .unwrap_or_default();
+    serde_json::from_str(&text).map_err(|e| format!("Invalid upload response: {e}"))
+}
+
+fn js_error(e: JsValue) -> String {
+    e.as_string().unwrap_or_else(|| format!("{e:?}"))
+}